@@ -0,0 +1,111 @@
+use std::sync::Arc;
+
+use axum::{ http::{ header, HeaderMap, StatusCode }, response::IntoResponse, Extension, Json };
+use bson::doc;
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{ apphandler::AppHandler, structs::{ apierror::APIError, passkey::Passkey }, util::{ cookies, cors::cors, ip::get_ip_from_request, token, webauthn } };
+
+#[derive(Deserialize)]
+pub struct AddPasskeyRequestBody{
+  pub challenge_id: String,
+  pub name: String,
+  pub client_data_json: String,
+  pub attestation_object: String
+}
+
+pub async fn put(
+  headers: HeaderMap,
+  Extension(app): Extension<Arc<AppHandler>>,
+  Json(body): Json<AddPasskeyRequestBody>
+) -> impl IntoResponse{
+  let cookies = headers.get("cookie");
+  if cookies.is_none() { return Err(APIError::default(&headers)) }
+
+  let cookies = cookies.unwrap().to_str().unwrap().to_owned();
+  let cookies = cookies::parse(cookies);
+
+  let token = cookies.get("token").unwrap().clone();
+
+  let identity = token::identify(token, app.clone(), get_ip_from_request(&headers).unwrap()).await;
+  if identity.is_err() { return Err(APIError::new(500, identity.unwrap_err().to_string(), &headers)) }
+
+  let ( user, session ) = identity.unwrap();
+  let verified = token::verified(&user, &session);
+
+  if verified.is_err() {
+    return Ok((
+      StatusCode::OK,
+      [
+        ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+        ( header::ACCESS_CONTROL_ALLOW_METHODS, "GET".into() ),
+        ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+      ],
+      Json(verified.unwrap_err())
+    ))
+  }
+
+  let elevated = token::elevated(&user, &session);
+
+  if elevated.is_err() {
+    return Ok((
+      StatusCode::OK,
+      [
+        ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+        ( header::ACCESS_CONTROL_ALLOW_METHODS, "GET".into() ),
+        ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+      ],
+      Json(elevated.unwrap_err())
+    ))
+  }
+
+  if user.passkeys.len() >= webauthn::MAX_PASSKEYS { return Err(APIError::new(400, "Too many passkeys".into(), &headers)) }
+
+  let name = body.name.trim().to_owned();
+  if name.is_empty() || name.len() > 50 { return Err(APIError::new(400, "Invalid Name".into(), &headers)) }
+
+  let challenge = webauthn::take_challenge(&app, &body.challenge_id, "register").await;
+  if challenge.is_none(){ return Err(APIError::new(400, "Invalid Challenge".into(), &headers)) }
+
+  let challenge = challenge.unwrap();
+  if challenge.user_id != Some(user._id){ return Err(APIError::new(400, "Invalid Challenge".into(), &headers)) }
+
+  let Ok(credential) = webauthn::verify_registration(&challenge, &body.client_data_json, &body.attestation_object) else {
+    return Err(APIError::new(400, "Invalid Passkey".into(), &headers)) };
+
+  // Credential IDs are how we find the user on login, so they can only belong to one account
+  let existing = app.users.count_documents(doc! { "passkeys.id": &credential.id }).await.unwrap();
+  if existing > 0 { return Err(APIError::new(400, "Passkey already registered".into(), &headers)) }
+
+  let now = Utc::now().timestamp();
+  let passkey = Passkey {
+    id: credential.id,
+    name,
+
+    public_key: credential.public_key,
+    sign_count: credential.sign_count,
+
+    created_on: now,
+    last_used: now
+  };
+
+  app.users.update_one(doc! { "_id": user._id }, doc! {
+    "$push": { "passkeys": bson::to_bson(&passkey).unwrap() }
+  }).await.unwrap();
+
+  Ok((
+    StatusCode::OK,
+    [
+      ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+      ( header::ACCESS_CONTROL_ALLOW_METHODS, "GET".into() ),
+      ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+    ],
+    Json(json!({
+      "id": passkey.id,
+      "name": passkey.name,
+      "created_on": passkey.created_on
+    }))
+  ))
+}
\ No newline at end of file
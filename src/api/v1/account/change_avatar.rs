@@ -5,7 +5,7 @@ use chrono::Utc;
 use serde_json::json;
 use bson::doc;
 
-use crate::{ apphandler::AppHandler, structs::apierror::APIError, util::{ avatar, cookies, cors::cors, ip::get_ip_from_request, token } };
+use crate::{ apphandler::AppHandler, structs::apierror::APIError, util::{ avatar, cookies, cors::cors, ip::get_ip_from_request, token, webhook } };
 
 pub async fn put( 
   headers: HeaderMap,
@@ -44,13 +44,18 @@ pub async fn put(
   let file = multipart.next_field().await.unwrap().unwrap();
   if file.content_type().unwrap() != "image/png" { return Err(APIError::default(&headers)) }
 
-  let res = avatar::upload(user._id, user.avatar, file, app.r2()).await;
+  let res = avatar::upload(user._id, user.avatar.clone(), file, app.r2()).await;
   if res.is_err() { return Err(APIError::new(500, "Could not upload avatar".into(), &headers)) }
 
   app.users.update_one(doc! { "_id": user._id }, doc! { "$set": {
     "last_avatar_change": now,
     "avatar": res.unwrap()
   } }).await.unwrap();
+
+  webhook::send_to_user_apps(app.clone(), &user, "profile.updated", json!({
+    "user_id": user._id.to_hex(),
+    "fields": [ "avatar" ]
+  })).await;
   
   Ok((
     StatusCode::OK,
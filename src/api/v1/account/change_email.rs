@@ -46,6 +46,20 @@ pub async fn put(
     ))
   }
 
+  let elevated = token::elevated(&user, &session);
+
+  if elevated.is_err() {
+    return Ok((
+      StatusCode::OK,
+      [
+        ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+        ( header::ACCESS_CONTROL_ALLOW_METHODS, "GET".into() ),
+        ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+      ],
+      Json(elevated.unwrap_err())
+    ))
+  }
+
   if body.value.eq("") { return Err(APIError::new(400, "NO.".into(), &headers)); }
 
   let now = Utc::now().timestamp();
@@ -6,7 +6,7 @@ use serde::Deserialize;
 use serde_json::json;
 use bson::doc;
 
-use crate::{ apphandler::AppHandler, structs::{apierror::APIError, tunnel::TurnstileRes}, util::{ cookies, cors::cors, ip::get_ip_from_request, token } };
+use crate::{ apphandler::AppHandler, structs::{apierror::APIError, tunnel::TurnstileRes}, util::{ cookies, cors::cors, ip::get_ip_from_request, token, webhook } };
 
 #[derive(Deserialize)]
 pub struct ChangeUsernameRequest{
@@ -67,10 +67,15 @@ pub async fn put(
     return Err(APIError::new(400, "Username already in use.".into(), &headers)); }
 
   app.users.update_one(doc! { "_id": user._id }, doc! { "$set": {
-    "username": body.value,
+    "username": &body.value,
     "last_username_change": now
   } }).await.unwrap();
 
+  webhook::send_to_user_apps(app.clone(), &user, "profile.updated", json!({
+    "user_id": user._id.to_hex(),
+    "fields": [ "username" ]
+  })).await;
+
   Ok((
     StatusCode::OK,
     [
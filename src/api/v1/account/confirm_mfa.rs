@@ -46,6 +46,20 @@ pub async fn put(
     ))
   }
 
+  let elevated = token::elevated(&user, &session);
+
+  if elevated.is_err() {
+    return Ok((
+      StatusCode::OK,
+      [
+        ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+        ( header::ACCESS_CONTROL_ALLOW_METHODS, "GET".into() ),
+        ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+      ],
+      Json(elevated.unwrap_err())
+    ))
+  }
+
   let account_secret = user.mfa_string.clone().unwrap();
   let account_secret = Secret::Raw(encrypt::decrypt_from_user(&user, account_secret).as_bytes().to_vec());
 
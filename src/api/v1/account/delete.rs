@@ -5,7 +5,7 @@ use chrono::Utc;
 use serde_json::json;
 use bson::doc;
 
-use crate::{ apphandler::AppHandler, structs::apierror::APIError, util::{ cookies, cors::cors, email, ip::get_ip_from_request, token } };
+use crate::{ apphandler::AppHandler, structs::apierror::APIError, util::{ cookies, cors::cors, email, ip::get_ip_from_request, token, webhook } };
 
 pub async fn del(
   headers: HeaderMap,
@@ -37,11 +37,32 @@ pub async fn del(
     ))
   }
 
+  let elevated = token::elevated(&user, &session);
+
+  if elevated.is_err() {
+    return Ok((
+      StatusCode::OK,
+      [
+        ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+        ( header::ACCESS_CONTROL_ALLOW_METHODS, "GET".into() ),
+        ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+      ],
+      Json(elevated.unwrap_err())
+    ))
+  }
+
   let now = Utc::now().timestamp();
   app.users.update_one(doc! { "_id": user._id }, doc! { "$set": { "deletion_flagged_after": Some(now + 86400) } }).await.unwrap();
 
   app.sessions.delete_many(doc! { "user_id": user._id }).await.unwrap();
   app.oauth_sessions.delete_many(doc! { "user_id": user._id }).await.unwrap();
+  app.oauth_codes.delete_many(doc! { "user_id": user._id }).await.unwrap();
+  app.oauth_device_codes.delete_many(doc! { "user_id": user._id }).await.unwrap();
+
+  webhook::send_to_user_apps(app.clone(), &user, "user.deleted", json!({
+    "user_id": user._id.to_hex(),
+    "deletes_at": now + 86400
+  })).await;
 
   email::send(
     ( user.username.as_str(), user.email.as_str() ),
@@ -36,6 +36,20 @@ pub async fn delete(
     ))
   }
 
+  let elevated = token::elevated(&user, &session);
+
+  if elevated.is_err() {
+    return Ok((
+      StatusCode::OK,
+      [
+        ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+        ( header::ACCESS_CONTROL_ALLOW_METHODS, "GET".into() ),
+        ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+      ],
+      Json(elevated.unwrap_err())
+    ))
+  }
+
   if user.has_mfa{
     app.users.update_one(doc! { "_id": user._id }, doc! { "$set": {
       "has_mfa": false,
@@ -0,0 +1,79 @@
+use std::sync::Arc;
+
+use axum::{ extract::Query, http::{ header, HeaderMap, StatusCode }, response::IntoResponse, Extension, Json };
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{ apphandler::AppHandler, structs::apierror::APIError, util::{ cookies, cors::cors, ip::get_ip_from_request, token, upstream } };
+
+#[derive(Deserialize)]
+pub struct LinkProviderRequestQuery{
+  pub provider: String
+}
+
+pub async fn get(
+  headers: HeaderMap,
+  Query(query): Query<LinkProviderRequestQuery>,
+  Extension(app): Extension<Arc<AppHandler>>
+) -> impl IntoResponse{
+  let cookies = headers.get("cookie");
+  if cookies.is_none() { return Err(APIError::default(&headers)) }
+
+  let cookies = cookies.unwrap().to_str().unwrap().to_owned();
+  let cookies = cookies::parse(cookies);
+
+  let token = cookies.get("token").unwrap().clone();
+
+  let identity = token::identify(token, app.clone(), get_ip_from_request(&headers).unwrap()).await;
+  if identity.is_err() { return Err(APIError::new(500, identity.unwrap_err().to_string(), &headers)) }
+
+  let ( user, session ) = identity.unwrap();
+  let verified = token::verified(&user, &session);
+
+  if verified.is_err() {
+    return Ok((
+      StatusCode::OK,
+      [
+        ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+        ( header::ACCESS_CONTROL_ALLOW_METHODS, "GET".into() ),
+        ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+      ],
+      Json(verified.unwrap_err())
+    ))
+  }
+
+  let elevated = token::elevated(&user, &session);
+
+  if elevated.is_err() {
+    return Ok((
+      StatusCode::OK,
+      [
+        ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+        ( header::ACCESS_CONTROL_ALLOW_METHODS, "GET".into() ),
+        ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+      ],
+      Json(elevated.unwrap_err())
+    ))
+  }
+
+  let provider = upstream::find(&app, &query.provider);
+  if provider.is_none(){ return Err(APIError::new(404, "Unknown Provider".into(), &headers)) }
+
+  let provider = provider.unwrap();
+  if user.linked_identities.iter().any(| x | x.provider == provider.id){
+    return Err(APIError::new(400, format!("{} is already linked", provider.name), &headers)) }
+
+  let redirect = upstream::start(&app, provider, "link", Some(user._id), None).await;
+
+  Ok((
+    StatusCode::OK,
+    [
+      ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+      ( header::ACCESS_CONTROL_ALLOW_METHODS, "GET".into() ),
+      ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+    ],
+    Json(json!({
+      "redirect": redirect
+    }))
+  ))
+}
\ No newline at end of file
@@ -0,0 +1,57 @@
+use std::sync::Arc;
+
+use axum::{ http::{ header, HeaderMap, StatusCode }, response::IntoResponse, Extension, Json };
+use serde_json::json;
+
+use crate::{ apphandler::AppHandler, structs::apierror::APIError, util::{ cookies, cors::cors, ip::get_ip_from_request, token, upstream } };
+
+pub async fn get(
+  headers: HeaderMap,
+  Extension(app): Extension<Arc<AppHandler>>
+) -> impl IntoResponse{
+  let cookies = headers.get("cookie");
+  if cookies.is_none() { return Err(APIError::default(&headers)) }
+
+  let cookies = cookies.unwrap().to_str().unwrap().to_owned();
+  let cookies = cookies::parse(cookies);
+
+  let token = cookies.get("token").unwrap().clone();
+
+  let identity = token::identify(token, app.clone(), get_ip_from_request(&headers).unwrap()).await;
+  if identity.is_err() { return Err(APIError::new(500, identity.unwrap_err().to_string(), &headers)) }
+
+  let ( user, session ) = identity.unwrap();
+  let verified = token::verified(&user, &session);
+
+  if verified.is_err() {
+    return Ok((
+      StatusCode::OK,
+      [
+        ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+        ( header::ACCESS_CONTROL_ALLOW_METHODS, "GET".into() ),
+        ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+      ],
+      Json(verified.unwrap_err())
+    ))
+  }
+
+  let identities: Vec<_> = user.linked_identities.iter()
+    .map(| x | json!({
+      "provider": x.provider,
+      "name": upstream::find(&app, &x.provider).map(| x | x.name.clone()),
+      "username": x.username,
+      "email": x.email,
+      "linked_on": x.linked_on
+    }))
+    .collect();
+
+  Ok((
+    StatusCode::OK,
+    [
+      ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+      ( header::ACCESS_CONTROL_ALLOW_METHODS, "GET".into() ),
+      ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+    ],
+    Json(json!(identities))
+  ))
+}
\ No newline at end of file
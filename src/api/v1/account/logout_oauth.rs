@@ -41,11 +41,18 @@ pub async fn get(
   if specific_session.is_some(){
     let specific_session = specific_session.unwrap();
 
-    app.oauth_sessions.delete_one(doc! {
+    let oauth_session = app.oauth_sessions.find_one_and_delete(doc! {
       "_id": ObjectId::parse_str(specific_session).unwrap(),
       "user_id": user._id // Include user ID to only let users delete their sessions.
     }).await.unwrap();
 
+    // Also revoke the refresh token for that device, so it can't just get a new session
+    if let Some(oauth_session) = oauth_session {
+      if oauth_session.grant_id.is_some(){
+        app.oauth_codes.delete_many(doc! { "user_id": user._id, "grant_id": oauth_session.grant_id }).await.unwrap();
+      }
+    }
+
     Ok((
       StatusCode::OK,
       [
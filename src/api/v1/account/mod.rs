@@ -12,4 +12,14 @@ pub mod logout_oauth;
 pub mod delete;
 pub mod deletion_state;
 pub mod restore;
-pub mod remove_oauth_app;
\ No newline at end of file
+pub mod remove_oauth_app;
+pub mod passkeys;
+pub mod passkey_options;
+pub mod add_passkey;
+pub mod remove_passkey;
+pub mod linked_identities;
+pub mod link_provider;
+pub mod unlink_provider;
+pub mod reauthenticate_options;
+pub mod reauthenticate;
+pub mod reauthenticate_upstream;
\ No newline at end of file
@@ -0,0 +1,69 @@
+use std::sync::Arc;
+
+use axum::{ http::{ header, HeaderMap, StatusCode }, response::IntoResponse, Extension, Json };
+use base64::{ prelude::BASE64_URL_SAFE_NO_PAD, Engine };
+use serde_json::json;
+
+use crate::{ apphandler::AppHandler, structs::apierror::APIError, util::{ cookies, cors::cors, ip::get_ip_from_request, token, webauthn } };
+
+pub async fn get(
+  headers: HeaderMap,
+  Extension(app): Extension<Arc<AppHandler>>
+) -> impl IntoResponse{
+  let cookies = headers.get("cookie");
+  if cookies.is_none() { return Err(APIError::default(&headers)) }
+
+  let cookies = cookies.unwrap().to_str().unwrap().to_owned();
+  let cookies = cookies::parse(cookies);
+
+  let token = cookies.get("token").unwrap().clone();
+
+  let identity = token::identify(token, app.clone(), get_ip_from_request(&headers).unwrap()).await;
+  if identity.is_err() { return Err(APIError::new(500, identity.unwrap_err().to_string(), &headers)) }
+
+  let ( user, session ) = identity.unwrap();
+  let verified = token::verified(&user, &session);
+
+  if verified.is_err() {
+    return Ok((
+      StatusCode::OK,
+      [
+        ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+        ( header::ACCESS_CONTROL_ALLOW_METHODS, "GET".into() ),
+        ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+      ],
+      Json(verified.unwrap_err())
+    ))
+  }
+
+  if user.passkeys.len() >= webauthn::MAX_PASSKEYS { return Err(APIError::new(400, "Too many passkeys".into(), &headers)) }
+
+  let Some(rp_id) = webauthn::rp_id(&headers) else { return Err(APIError::new(400, "Invalid Origin".into(), &headers)) };
+  let challenge = webauthn::new_challenge(&app, "register", rp_id, Some(user._id), None).await;
+
+  Ok((
+    StatusCode::OK,
+    [
+      ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+      ( header::ACCESS_CONTROL_ALLOW_METHODS, "GET".into() ),
+      ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+    ],
+    Json(json!({
+      "challenge_id": challenge._id.to_hex(),
+      "publicKey": {
+        "challenge": challenge.challenge,
+        "rp": { "id": challenge.rp_id, "name": "Phaze ID" },
+        "user": {
+          "id": BASE64_URL_SAFE_NO_PAD.encode(user._id.bytes()),
+          "name": user.username,
+          "displayName": user.username
+        },
+        "pubKeyCredParams": [ { "type": "public-key", "alg": -7 } ],
+        "timeout": webauthn::CHALLENGE_LIFETIME * 1000,
+        "attestation": "none",
+        "excludeCredentials": user.passkeys.iter().map(| x | json!({ "type": "public-key", "id": x.id })).collect::<Vec<_>>(),
+        "authenticatorSelection": { "residentKey": "required", "userVerification": "preferred" }
+      }
+    }))
+  ))
+}
\ No newline at end of file
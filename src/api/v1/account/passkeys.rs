@@ -0,0 +1,51 @@
+use std::sync::Arc;
+
+use axum::{ http::{ header, HeaderMap, StatusCode }, response::IntoResponse, Extension, Json };
+use serde_json::json;
+
+use crate::{ apphandler::AppHandler, structs::apierror::APIError, util::{ cookies, cors::cors, ip::get_ip_from_request, token } };
+
+pub async fn get(
+  headers: HeaderMap,
+  Extension(app): Extension<Arc<AppHandler>>
+) -> impl IntoResponse{
+  let cookies = headers.get("cookie");
+  if cookies.is_none() { return Err(APIError::default(&headers)) }
+
+  let cookies = cookies.unwrap().to_str().unwrap().to_owned();
+  let cookies = cookies::parse(cookies);
+
+  let token = cookies.get("token").unwrap().clone();
+
+  let identity = token::identify(token, app.clone(), get_ip_from_request(&headers).unwrap()).await;
+  if identity.is_err() { return Err(APIError::new(500, identity.unwrap_err().to_string(), &headers)) }
+
+  let ( user, session ) = identity.unwrap();
+  let verified = token::verified(&user, &session);
+
+  if verified.is_err() {
+    return Ok((
+      StatusCode::OK,
+      [
+        ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+        ( header::ACCESS_CONTROL_ALLOW_METHODS, "GET".into() ),
+        ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+      ],
+      Json(verified.unwrap_err())
+    ))
+  }
+
+  let passkeys: Vec<_> = user.passkeys.iter()
+    .map(| x | json!({ "id": x.id, "name": x.name, "created_on": x.created_on, "last_used": x.last_used }))
+    .collect();
+
+  Ok((
+    StatusCode::OK,
+    [
+      ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+      ( header::ACCESS_CONTROL_ALLOW_METHODS, "GET".into() ),
+      ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+    ],
+    Json(json!(passkeys))
+  ))
+}
\ No newline at end of file
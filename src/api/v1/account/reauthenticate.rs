@@ -0,0 +1,139 @@
+use std::sync::Arc;
+
+use argon2::{ password_hash::Encoding, Argon2, PasswordHash, PasswordVerifier };
+use axum::{ http::{ header, HeaderMap, StatusCode }, response::IntoResponse, Extension, Json };
+use bson::doc;
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::json;
+use totp_rs::{ Algorithm, Secret, TOTP };
+
+use crate::{ apphandler::AppHandler, structs::{ apierror::APIError, passkey::PasskeyAssertion, session::Session, user::User }, util::{ cookies, cors::cors, encrypt, ip::get_ip_from_request, ratelimit, token, webauthn } };
+
+#[derive(Deserialize)]
+pub struct ReauthenticateRequestBody{
+  pub method: String,
+
+  pub password: Option<String>,
+  pub code: Option<String>,
+  pub passkey: Option<PasskeyAssertion>
+}
+
+pub async fn put(
+  headers: HeaderMap,
+  Extension(app): Extension<Arc<AppHandler>>,
+  Json(body): Json<ReauthenticateRequestBody>
+) -> impl IntoResponse{
+  let cookies = headers.get("cookie");
+  if cookies.is_none() { return Err(APIError::default(&headers)) }
+
+  let cookies = cookies.unwrap().to_str().unwrap().to_owned();
+  let cookies = cookies::parse(cookies);
+
+  let token = cookies.get("token").unwrap().clone();
+
+  let identity = token::identify(token, app.clone(), get_ip_from_request(&headers).unwrap()).await;
+  if identity.is_err() { return Err(APIError::new(500, identity.unwrap_err().to_string(), &headers)) }
+
+  let ( user, session ) = identity.unwrap();
+  let verified = token::verified(&user, &session);
+
+  if verified.is_err() {
+    return Ok((
+      StatusCode::OK,
+      [
+        ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+        ( header::ACCESS_CONTROL_ALLOW_METHODS, "GET".into() ),
+        ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+      ],
+      Json(verified.unwrap_err())
+    ))
+  }
+
+  // Throttled per session rather than locking the account, otherwise a stolen cookie could lock the owner out
+  let hits = match ratelimit::begin(&app, &[ ( &ratelimit::REAUTH_SESSION, session._id.to_hex() ) ]).await {
+    Ok(hits) => hits,
+    Err(retry_at) => return Err(APIError::new(429, format!("Too many attempts, try again at {}", retry_at), &headers))
+  };
+
+  let valid = match body.method.as_str() {
+    "password" => check_password(&user, body.password),
+    "totp" => check_totp(&user, body.code),
+    "backup_code" => check_backup_code(&user, body.code, &app).await,
+    "passkey" => check_passkey(&user, &session, body.passkey, &app).await,
+    _ => return Err(APIError::new(400, "Invalid Method".into(), &headers))
+  };
+
+  if !valid { return Err(APIError::new(400, "Invalid Credentials".into(), &headers)) }
+  ratelimit::forget(&app, hits).await;
+
+  let elevated_until = Utc::now().timestamp() + token::ELEVATION_LIFETIME;
+
+  app.sessions.update_one(doc! { "_id": session._id }, doc! { "$set": { "elevated_until": elevated_until } }).await.unwrap();
+
+  Ok((
+    StatusCode::OK,
+    [
+      ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+      ( header::ACCESS_CONTROL_ALLOW_METHODS, "GET".into() ),
+      ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+    ],
+    Json(json!({
+      "ok": true,
+      "elevated_until": elevated_until
+    }))
+  ))
+}
+
+fn check_password( user: &User, password: Option<String> ) -> bool{
+  let Some(password) = password else { return false };
+  Argon2::default().verify_password(password.as_bytes(), &PasswordHash::parse(&user.password, Encoding::B64).unwrap()).is_ok()
+}
+
+fn check_totp( user: &User, code: Option<String> ) -> bool{
+  let ( Some(code), Some(mfa_string) ) = ( code, user.mfa_string.clone() ) else { return false };
+  if !user.has_mfa { return false }
+
+  let account_secret = Secret::Raw(encrypt::decrypt_from_user(user, mfa_string).as_bytes().to_vec());
+
+  let totp = TOTP::new(
+    Algorithm::SHA1,
+    6, 1, 30,
+    account_secret.to_bytes().unwrap(),
+    Some("Phaze ID".to_string()),
+    user.username.clone()
+  ).unwrap();
+
+  totp.check_current(&code).unwrap()
+}
+
+// Backup codes are single use here too
+async fn check_backup_code( user: &User, code: Option<String>, app: &AppHandler ) -> bool{
+  let Some(code) = code else { return false };
+  if !user.has_mfa { return false }
+
+  let argon2 = Argon2::default();
+  for code_hash in &user.backup_codes {
+    if argon2.verify_password(code.as_bytes(), &PasswordHash::parse(code_hash, Encoding::B64).unwrap()).is_ok() {
+      app.users.update_one(doc! { "_id": user._id }, doc! { "$pull": { "backup_codes": code_hash } }).await.unwrap();
+      return true
+    }
+  }
+
+  false
+}
+
+async fn check_passkey( user: &User, session: &Session, assertion: Option<PasskeyAssertion>, app: &AppHandler ) -> bool{
+  let Some(assertion) = assertion else { return false };
+
+  // The challenge has to have been made for this session, see /account/reauthenticate_options
+  let Some(challenge) = webauthn::take_challenge(app, &assertion.challenge_id, "reauthenticate").await else { return false };
+  if challenge.session_id != Some(session._id) { return false }
+
+  let Some(passkey) = webauthn::find_passkey(user, &assertion.credential_id) else { return false };
+
+  let Ok(sign_count) = webauthn::verify_assertion(&challenge, passkey, &assertion, false) else { return false };
+  webauthn::update_sign_count(app, user._id, &passkey.id, sign_count).await;
+
+  true
+}
\ No newline at end of file
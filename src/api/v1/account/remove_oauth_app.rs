@@ -4,7 +4,7 @@ use axum::{ extract::Query, http::{ header, HeaderMap, StatusCode }, response::I
 use serde_json::json;
 use bson::{doc, oid::ObjectId};
 
-use crate::{ apphandler::AppHandler, structs::apierror::APIError, util::{ cookies, cors::cors, ip::get_ip_from_request, token } };
+use crate::{ apphandler::AppHandler, structs::apierror::APIError, util::{ consent, cookies, cors::cors, ip::get_ip_from_request, token, webhook } };
 
 pub async fn get( 
   headers: HeaderMap,
@@ -37,6 +37,20 @@ pub async fn get(
     ))
   }
 
+  let elevated = token::elevated(&user, &session);
+
+  if elevated.is_err() {
+    return Ok((
+      StatusCode::OK,
+      [
+        ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+        ( header::ACCESS_CONTROL_ALLOW_METHODS, "GET".into() ),
+        ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+      ],
+      Json(elevated.unwrap_err())
+    ))
+  }
+
   let specific_session = query.get("session");
   if specific_session.is_some(){
     let specific_session = specific_session.unwrap();
@@ -52,12 +66,22 @@ pub async fn get(
       "app_id": session.app_id
     }).await.unwrap();
 
+    // Refresh tokens would otherwise let the app start new sessions
+    app.oauth_codes.delete_many(doc! { "user_id": user._id, "app": session.app_id }).await.unwrap();
+    app.oauth_device_codes.delete_many(doc! { "user_id": user._id, "app": session.app_id }).await.unwrap();
+
     // Remove app from user
     app.users.update_one(doc! { "_id": user._id }, doc! {
       "$pull": { "allowed_apps": session.app_id },
       "$push": { "apps_to_delete_data": session.app_id }
     }).await.unwrap();
 
+    consent::revoke(&app, user._id, session.app_id).await;
+
+    webhook::send(app.clone(), session.app_id, "grant.revoked", json!({
+      "user_id": user._id.to_hex()
+    })).await;
+
     Ok((
       StatusCode::OK,
       [
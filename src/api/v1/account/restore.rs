@@ -5,7 +5,7 @@ use chrono::Utc;
 use serde_json::json;
 use bson::doc;
 
-use crate::{ apphandler::AppHandler, structs::apierror::APIError, util::{ cookies, cors::cors, email, ip::get_ip_from_request, token } };
+use crate::{ apphandler::AppHandler, structs::apierror::APIError, util::{ cookies, cors::cors, email, ip::get_ip_from_request, token, webhook } };
 
 pub async fn get(
   headers: HeaderMap,
@@ -45,6 +45,11 @@ pub async fn get(
 
   app.users.update_one(doc! { "_id": user._id }, doc! { "$set": { "deletion_flagged_after": None::<i64> } }).await.unwrap();
 
+  // Apps were told about the deletion, so tell them it's off
+  webhook::send_to_user_apps(app.clone(), &user, "user.restored", json!({
+    "user_id": user._id.to_hex()
+  })).await;
+
   email::send(
     ( user.username.as_str(), user.email.as_str() ),
     "Welcome Back!",
@@ -5,7 +5,7 @@ use serde::Deserialize;
 use serde_json::json;
 use bson::doc;
 
-use crate::{ apphandler::AppHandler, structs::apierror::APIError, util::{ cookies, cors::cors, ip::get_ip_from_request, token } };
+use crate::{ apphandler::AppHandler, structs::apierror::APIError, util::{ cookies, cors::cors, ip::get_ip_from_request, token, webhook } };
 
 #[derive(Deserialize)]
 pub struct VerifyEmailRequest{
@@ -49,10 +49,16 @@ pub async fn put(
       doc! { "$set": { 
         "email_update.email": "",
         "email_update.verification_code": "",
-        "email": user.email_update.email
+        "email": &user.email_update.email
       } }
     ).await.unwrap();
 
+    // Only say what changed, apps without the email scope have to ask for it
+    webhook::send_to_user_apps(app.clone(), &user, "profile.updated", json!({
+      "user_id": user._id.to_hex(),
+      "fields": [ "email" ]
+    })).await;
+
     Ok((
       StatusCode::OK,
       [
@@ -0,0 +1,2 @@
+pub mod rotate_keys;
+pub mod update_app;
\ No newline at end of file
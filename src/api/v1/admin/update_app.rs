@@ -0,0 +1,113 @@
+use std::sync::Arc;
+
+use axum::{ http::{ header, HeaderMap, StatusCode }, response::IntoResponse, Extension, Json };
+use bson::{ doc, oid::ObjectId };
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{ apphandler::AppHandler, structs::{ apierror::APIError, oauthapp::PublicOAuthApplication, securityevent::SecurityEvent }, util::{ cookies, cors::cors, ip::get_ip_from_request, token } };
+
+#[derive(Deserialize)]
+pub struct AdminUpdateAppRequest{
+  pub id: String,
+  pub verified: Option<bool>,
+  pub allow_skip: Option<bool>,
+  pub suspended: Option<bool>,
+  pub reason: Option<String>
+}
+
+pub async fn put(
+  headers: HeaderMap,
+  Extension(app): Extension<Arc<AppHandler>>,
+  Json(body): Json<AdminUpdateAppRequest>
+) -> impl IntoResponse{
+  let cookies = headers.get("cookie");
+  if cookies.is_none() { return Err(APIError::default(&headers)) }
+
+  let cookies = cookies.unwrap().to_str().unwrap().to_owned();
+  let cookies = cookies::parse(cookies);
+
+  let token = cookies.get("token").unwrap().clone();
+
+  let identity = token::identify(token, app.clone(), get_ip_from_request(&headers).unwrap()).await;
+  if identity.is_err() { return Err(APIError::new(500, identity.unwrap_err().to_string(), &headers)) }
+
+  let ( user, session ) = identity.unwrap();
+  let verified = token::verified(&user, &session);
+
+  if verified.is_err() {
+    return Ok((
+      StatusCode::OK,
+      [
+        ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+        ( header::ACCESS_CONTROL_ALLOW_METHODS, "GET".into() ),
+        ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+      ],
+      Json(verified.unwrap_err())
+    ))
+  }
+
+  if !user.roles.contains(&"ADMIN".to_string()){ return Err(APIError::new(404, "nothing to see here".into(), &headers)) }
+
+  let app_id = ObjectId::parse_str(&body.id);
+  if app_id.is_err(){ return Err(APIError::new(404, "App not found".into(), &headers)) }
+
+  let oauth_app = app.oauth_apps.find_one(doc! { "_id": app_id.unwrap() }).await.unwrap();
+  if oauth_app.is_none(){ return Err(APIError::new(404, "App not found".into(), &headers)) }
+
+  let mut oauth_app = oauth_app.unwrap();
+
+  if let Some(verified) = body.verified {
+    oauth_app.verified = verified;
+  }
+
+  if let Some(allow_skip) = body.allow_skip {
+    oauth_app.allow_skip = allow_skip;
+  }
+
+  if let Some(suspended) = body.suspended {
+    if suspended && !oauth_app.suspended {
+      // Everything the app was handed out stops working straight away
+      app.oauth_sessions.delete_many(doc! { "app_id": oauth_app._id }).await.unwrap();
+      app.oauth_codes.delete_many(doc! { "app": oauth_app._id }).await.unwrap();
+      app.oauth_device_codes.delete_many(doc! { "app": oauth_app._id }).await.unwrap();
+    }
+
+    if suspended != oauth_app.suspended {
+      app.security_events.insert_one(SecurityEvent {
+        _id: ObjectId::new(),
+        event: if suspended { "app_suspended".into() } else { "app_unsuspended".into() },
+
+        user_id: Some(user._id),
+        app_id: Some(oauth_app._id),
+        grant_id: None,
+
+        ip: get_ip_from_request(&headers).ok(),
+        created_on: Utc::now().timestamp()
+      }).await.unwrap();
+    }
+
+    oauth_app.suspended = suspended;
+    oauth_app.suspended_reason = if suspended { body.reason } else { None };
+  }
+
+  app.oauth_apps.update_one(doc! { "_id": oauth_app._id }, doc! {
+    "$set": {
+      "verified": oauth_app.verified,
+      "allow_skip": oauth_app.allow_skip,
+      "suspended": oauth_app.suspended,
+      "suspended_reason": &oauth_app.suspended_reason
+    }
+  }).await.unwrap();
+
+  Ok((
+    StatusCode::OK,
+    [
+      ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+      ( header::ACCESS_CONTROL_ALLOW_METHODS, "GET".into() ),
+      ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+    ],
+    Json(json!(PublicOAuthApplication::from_app(oauth_app)))
+  ))
+}
\ No newline at end of file
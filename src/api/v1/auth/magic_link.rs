@@ -0,0 +1,69 @@
+use std::{ env, fs, sync::Arc };
+
+use axum::{ http::{ header, HeaderMap, StatusCode }, response::IntoResponse, Extension, Json };
+use bson::doc;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{ apphandler::AppHandler, structs::{ apierror::APIError, tunnel::TurnstileRes }, util::{ cors::cors, email, ip::get_ip_from_request, login, magic_link, ratelimit } };
+
+#[derive(Deserialize)]
+pub struct MagicLinkRequestBody{
+  email: String,
+  token: String
+}
+
+pub async fn post(
+  headers: HeaderMap,
+  Extension(app): Extension<Arc<AppHandler>>,
+  Json(body): Json<MagicLinkRequestBody>
+) -> impl IntoResponse{
+  let client = reqwest::Client::new();
+  let dat = client.post("https://challenges.cloudflare.com/turnstile/v0/siteverify")
+    .body(serde_json::to_string(&json!({
+      "secret": env::var("CF_TURNSTILE_SECRET").unwrap(),
+      "response": body.token
+    })).unwrap())
+    .header("Content-Type", "application/json")
+    .send().await.unwrap().text().await.unwrap();
+
+  let dat: TurnstileRes = serde_json::from_str(&dat).unwrap();
+  if !dat.success { return Err(APIError::new(400, "Invalid Captcha.".into(), &headers)); }
+
+  // Same limits as password reset emails, they're keyed on the address so refusing doesn't say whether it has an account
+  let limits = ratelimit::email_limits(&get_ip_from_request(&headers).unwrap(), &body.email);
+
+  if let Err(retry_at) = ratelimit::begin(&app, &limits).await {
+    return Err(APIError::new(429, format!("Too many attempts, try again at {}", retry_at), &headers)) }
+
+  // Always answer the same way so this can't be used to find out who has an account
+  let user = app.users.find_one(doc! { "email": &body.email }).await.unwrap();
+
+  if let Some(user) = user {
+    if user.email_verified && !login::is_deleted(&user) {
+      if let Some(token) = magic_link::create(&app, &user, get_ip_from_request(&headers).ok()).await {
+        tokio::spawn(async move {
+          let res = email::send(
+            ( user.username.as_str(), user.email.as_str() ),
+            "PhazeID Login Link",
+            &fs::read_to_string("templates/email/magic_link.html").unwrap()
+              .replace("{{USERNAME}}", &user.username)
+              .replace("{{URL}}", &format!("https://id.phazed.xyz/magic-link#{}", token))
+          ).await;
+
+          if let Err(err) = res { log::error!("Failed to send magic link to {}: {}", user._id, err); }
+        });
+      }
+    }
+  }
+
+  Ok((
+    StatusCode::OK,
+    [
+      ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+      ( header::ACCESS_CONTROL_ALLOW_METHODS, "POST".into() ),
+      ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+    ],
+    Json(json!({ "ok": true }))
+  ))
+}
\ No newline at end of file
@@ -0,0 +1,46 @@
+use std::sync::Arc;
+
+use axum::{ http::{ header, HeaderMap, StatusCode }, response::IntoResponse, Extension, Json };
+use bson::doc;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{ apphandler::AppHandler, structs::apierror::APIError, util::{ cors::cors, ip::get_ip_from_request, login, magic_link } };
+
+#[derive(Deserialize)]
+pub struct MagicLinkRedeemRequestBody{
+  token: String
+}
+
+pub async fn post(
+  headers: HeaderMap,
+  Extension(app): Extension<Arc<AppHandler>>,
+  Json(body): Json<MagicLinkRedeemRequestBody>
+) -> impl IntoResponse{
+  let user_id = magic_link::redeem(&app, &body.token).await;
+  if user_id.is_none(){ return Err(APIError::new(400, "Invalid or expired link".into(), &headers)) }
+
+  let user = app.users.find_one(doc! { "_id": user_id.unwrap() }).await.unwrap();
+  if user.is_none(){ return Err(APIError::new(400, "Invalid or expired link".into(), &headers)) }
+
+  let user = user.unwrap();
+
+  if let Some(locked_until) = login::locked_until(&user, &app).await.unwrap(){
+    return Err(APIError::new(403, format!("Account locked until {}", locked_until), &headers)) }
+
+  if login::is_deleted(&user){ return Err(APIError::new(400, "Invalid or expired link".into(), &headers)) }
+
+  // Same as a password login, the session still has to go through verification (and MFA if it's on)
+  let ( token, session ) = login::create_session(&get_ip_from_request(&headers).unwrap(), &user, false, &app).await.unwrap();
+  app.users.update_one(doc! { "_id": user._id }, doc! { "$set": { "login_attempts": 0 } }).await.unwrap();
+
+  Ok((
+    StatusCode::OK,
+    [
+      ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+      ( header::ACCESS_CONTROL_ALLOW_METHODS, "POST".into() ),
+      ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+    ],
+    Json(json!({ "token": format!("{}{}", token, session._id) }))
+  ))
+}
\ No newline at end of file
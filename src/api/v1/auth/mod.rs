@@ -1 +1,8 @@
-pub mod tunnel;
\ No newline at end of file
+pub mod tunnel;
+pub mod passkey_options;
+pub mod passkey;
+pub mod magic_link;
+pub mod magic_link_redeem;
+pub mod upstream_providers;
+pub mod upstream_login;
+pub mod upstream_callback;
\ No newline at end of file
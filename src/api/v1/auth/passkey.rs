@@ -0,0 +1,54 @@
+use std::sync::Arc;
+
+use axum::{ http::{ header, HeaderMap, StatusCode }, response::IntoResponse, Extension, Json };
+use base64::{ prelude::BASE64_URL_SAFE_NO_PAD, Engine };
+use bson::doc;
+use serde_json::json;
+
+use crate::{ apphandler::AppHandler, structs::{ apierror::APIError, passkey::PasskeyAssertion }, util::{ cors::cors, ip::get_ip_from_request, login, webauthn } };
+
+pub async fn post(
+  headers: HeaderMap,
+  Extension(app): Extension<Arc<AppHandler>>,
+  Json(body): Json<PasskeyAssertion>
+) -> impl IntoResponse{
+  let challenge = webauthn::take_challenge(&app, &body.challenge_id, "login").await;
+  if challenge.is_none(){ return Err(APIError::new(400, "Invalid Challenge".into(), &headers)) }
+
+  let challenge = challenge.unwrap();
+
+  let user = app.users.find_one(doc! { "passkeys.id": &body.credential_id }).await.unwrap();
+  if user.is_none(){ return Err(APIError::new(400, "Invalid Passkey".into(), &headers)) }
+
+  let user = user.unwrap();
+
+  if let Some(user_handle) = &body.user_handle {
+    if *user_handle != BASE64_URL_SAFE_NO_PAD.encode(user._id.bytes()){ return Err(APIError::new(400, "Invalid Passkey".into(), &headers)) } }
+
+  if let Some(locked_until) = login::locked_until(&user, &app).await.unwrap(){
+    return Err(APIError::new(403, format!("Account locked until {}", locked_until), &headers)) }
+
+  if login::is_deleted(&user){ return Err(APIError::new(400, "Invalid Passkey".into(), &headers)) }
+
+  let passkey = webauthn::find_passkey(&user, &body.credential_id).unwrap();
+
+  // The passkey is both factors here, so the authenticator has to have checked it's really them
+  let sign_count = webauthn::verify_assertion(&challenge, passkey, &body, true);
+  if sign_count.is_err(){ return Err(APIError::new(400, "Invalid Passkey".into(), &headers)) }
+
+  webauthn::update_sign_count(&app, user._id, &passkey.id, sign_count.unwrap()).await;
+
+  let ( token, session ) = login::create_session(&get_ip_from_request(&headers).unwrap(), &user, true, &app).await.unwrap();
+  let token = format!("{}{}", token, session._id);
+
+  Ok((
+    StatusCode::OK,
+    [
+      ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+      ( header::ACCESS_CONTROL_ALLOW_METHODS, "POST".into() ),
+      ( header::SET_COOKIE, format!("token={}; Max-Age=604800; Domain=idapi-jye3bcyp.phazed.xyz; Path=/api; HttpOnly; Secure; SameSite=Strict", token) ),
+      ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+    ],
+    Json(json!({ "PROCEDURE": "NEXT", "token": token }))
+  ))
+}
\ No newline at end of file
@@ -0,0 +1,25 @@
+use std::sync::Arc;
+
+use axum::{ http::{ header, HeaderMap, StatusCode }, response::IntoResponse, Extension, Json };
+
+use crate::{ apphandler::AppHandler, structs::apierror::APIError, util::{ cors::cors, webauthn } };
+
+pub async fn get(
+  headers: HeaderMap,
+  Extension(app): Extension<Arc<AppHandler>>
+) -> impl IntoResponse{
+  let Some(rp_id) = webauthn::rp_id(&headers) else { return Err(APIError::new(400, "Invalid Origin".into(), &headers)) };
+
+  // Passwordless login, the authenticator picks the account so there's nothing to list
+  let challenge = webauthn::new_challenge(&app, "login", rp_id, None, None).await;
+
+  Ok((
+    StatusCode::OK,
+    [
+      ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+      ( header::ACCESS_CONTROL_ALLOW_METHODS, "GET".into() ),
+      ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+    ],
+    Json(webauthn::request_options(&challenge, &[], "required"))
+  ))
+}
\ No newline at end of file
@@ -4,7 +4,7 @@ use rsa::{ pkcs8::{DecodePublicKey, EncodePublicKey}, RsaPrivateKey, RsaPublicKe
 use serde_json::json;
 use std::{ env, sync::Arc };
 
-use crate::{ apphandler::AppHandler, structs::tunnel::{ ClientCommand, TurnstileRes }, util::{ change_password::{try_change_password, try_change_password_without_account, try_reset_password}, cookies, decrypt::decrypt, encrypt::encrypt, ip::get_ip_from_request, login::try_login, signup::try_signup } };
+use crate::{ apphandler::AppHandler, structs::tunnel::{ ClientCommand, TurnstileRes }, util::{ change_password::{try_change_password, try_change_password_without_account, try_reset_password}, cookies, decrypt::decrypt, encrypt::encrypt, ip::get_ip_from_request, login::try_login, ratelimit, signup::try_signup } };
 
 pub async fn get(
   headers: HeaderMap,
@@ -82,10 +82,21 @@ async fn handle_socket( mut ws: WebSocket, app: Arc<AppHandler>, headers: Header
       let username = decrypt(data.0.to_owned(), &priv_key).unwrap();
       let password = decrypt(data.1.to_owned(), &priv_key).unwrap();
 
-      try_login(
-        &get_ip_from_request(&headers).unwrap(),
-        username, password, &remote_pub_key, &mut ws, app.clone()
-      ).await.unwrap();
+      let ip = get_ip_from_request(&headers).unwrap();
+      let limits = [
+        ( &ratelimit::LOGIN_IP, ip.clone() ),
+        ( &ratelimit::LOGIN_USERNAME, username.clone() ),
+        ( &ratelimit::LOGIN_IP_USERNAME, format!("{}:{}", ip, username) )
+      ];
+
+      let hits = match ratelimit::begin(&app, &limits).await {
+        Ok(hits) => hits,
+        Err(retry_at) => return rate_limited(retry_at, &remote_pub_key, &mut ws).await
+      };
+
+      // Only failures count, so people logging in normally never get slowed down
+      let res = try_login(&ip, username, password, &remote_pub_key, &mut ws, app.clone()).await;
+      if res.is_ok(){ ratelimit::forget(&app, hits).await; }
     },
     "AS" => {
       let data = val.data.split_at(172);
@@ -95,6 +106,12 @@ async fn handle_socket( mut ws: WebSocket, app: Arc<AppHandler>, headers: Header
       let password = decrypt(data1.0.to_owned(), &priv_key).unwrap();
       let email = decrypt(data1.1.to_owned(), &priv_key).unwrap();
 
+      let limits = [ ( &ratelimit::SIGNUP_IP, get_ip_from_request(&headers).unwrap() ) ];
+
+      // Every signup counts, successful or not
+      if let Err(retry_at) = ratelimit::begin(&app, &limits).await {
+        return rate_limited(retry_at, &remote_pub_key, &mut ws).await }
+
       try_signup(
         &get_ip_from_request(&headers).unwrap(),
         username, password, email, &remote_pub_key, &mut ws, app.clone()
@@ -121,7 +138,13 @@ async fn handle_socket( mut ws: WebSocket, app: Arc<AppHandler>, headers: Header
     },
     "RP" => {
       let email = decrypt(val.data.to_owned(), &priv_key).unwrap();
-      
+
+      let limits = ratelimit::email_limits(&get_ip_from_request(&headers).unwrap(), &email);
+
+      // Every reset sends an email, so they all count
+      if let Err(retry_at) = ratelimit::begin(&app, &limits).await {
+        return rate_limited(retry_at, &remote_pub_key, &mut ws).await }
+
       try_reset_password(email, &remote_pub_key, &mut ws, app).await.unwrap();
     },
     "NP" => {
@@ -130,11 +153,25 @@ async fn handle_socket( mut ws: WebSocket, app: Arc<AppHandler>, headers: Header
       let token = data.0.to_owned();
       let password = decrypt(data.1.to_owned(), &priv_key).unwrap();
 
-      try_change_password_without_account(
+      let limits = [ ( &ratelimit::NEW_PASSWORD_IP, get_ip_from_request(&headers).unwrap() ) ];
+
+      let hits = match ratelimit::begin(&app, &limits).await {
+        Ok(hits) => hits,
+        Err(retry_at) => return rate_limited(retry_at, &remote_pub_key, &mut ws).await
+      };
+
+      let res = try_change_password_without_account(
         password, token, &remote_pub_key,
         &mut ws, app.clone()
-      ).await.unwrap();
+      ).await;
+
+      if res.is_ok(){ ratelimit::forget(&app, hits).await; }
     }
     _ => { return; }
   }
+}
+
+async fn rate_limited( retry_at: i64, remote_pub_key: &RsaPublicKey, ws: &mut WebSocket ){
+  // 1 - Error, 9 - Error Code "Too many attempts, try again at 000"
+  ws.send(Message::Text(encrypt(format!("19{}", retry_at), remote_pub_key).unwrap().into())).await.unwrap();
 }
\ No newline at end of file
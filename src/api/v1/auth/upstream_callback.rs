@@ -0,0 +1,150 @@
+use std::sync::Arc;
+
+use axum::{ http::{ header, HeaderMap, StatusCode }, response::IntoResponse, Extension, Json };
+use bson::doc;
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::{ json, Value };
+
+use crate::{ apphandler::AppHandler, structs::{ apierror::APIError, upstream::{ UpstreamProvider, UpstreamState } }, util::{ cookies, cors::cors, ip::get_ip_from_request, login, signup::SignupError, token, upstream::{ self, UpstreamUser } } };
+
+#[derive(Deserialize)]
+pub struct UpstreamCallbackRequestBody{
+  pub code: String,
+  pub state: String
+}
+
+pub async fn post(
+  headers: HeaderMap,
+  Extension(app): Extension<Arc<AppHandler>>,
+  Json(body): Json<UpstreamCallbackRequestBody>
+) -> impl IntoResponse{
+  let state = upstream::take_state(&app, &body.state).await;
+  if state.is_none(){ return Err(APIError::new(400, "Invalid State".into(), &headers)) }
+
+  let state = state.unwrap();
+
+  let provider = upstream::find(&app, &state.provider);
+  if provider.is_none(){ return Err(APIError::new(404, "Unknown Provider".into(), &headers)) }
+
+  let provider = provider.unwrap();
+
+  let upstream_user = upstream::exchange(provider, &body.code, state.code_verifier.clone()).await;
+  if upstream_user.is_err(){ return Err(APIError::new(400, format!("Could not log in with {}", provider.name), &headers)) }
+
+  let upstream_user = upstream_user.unwrap();
+
+  let res = match state.mode.as_str() {
+    "link" => link(&headers, &app, provider, &state, &upstream_user).await?,
+    "reauthenticate" => reauthenticate(&headers, &app, provider, &state, &upstream_user).await?,
+    _ => login(&headers, &app, provider, &upstream_user).await?
+  };
+
+  Ok((
+    StatusCode::OK,
+    [
+      ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+      ( header::ACCESS_CONTROL_ALLOW_METHODS, "POST".into() ),
+      ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+    ],
+    Json(res)
+  ))
+}
+
+async fn login( headers: &HeaderMap, app: &Arc<AppHandler>, provider: &UpstreamProvider, upstream_user: &UpstreamUser ) -> Result<Value, APIError>{
+  let mut new_account = false;
+
+  let user = match upstream::find_user(app, &provider.id, &upstream_user.subject).await {
+    Some(user) => user,
+    None => {
+      // Never attach to an existing account by email, they have to link it from their settings while logged in
+      match upstream::signup(app, provider, upstream_user).await {
+        Ok(user) => {
+          new_account = true;
+          user
+        },
+        Err(SignupError::EmailInUse) => return Err(APIError::new(409, format!("An account with this email already exists, log in and link {} from your account settings", provider.name), headers)),
+        Err(SignupError::InvalidEmail) => return Err(APIError::new(400, format!("{} didn't give us a verified email", provider.name), headers)),
+        Err(err) => return Err(APIError::new(400, err.message().into(), headers))
+      }
+    }
+  };
+
+  if let Some(locked_until) = login::locked_until(&user, app).await.unwrap(){
+    return Err(APIError::new(403, format!("Account locked until {}", locked_until), headers)) }
+
+  if login::is_deleted(&user){ return Err(APIError::new(400, "Account not found".into(), headers)) }
+
+  // Same as a password login, the session still has to go through verification (and MFA if it's on)
+  let ( token, session ) = login::create_session(&get_ip_from_request(headers).unwrap(), &user, false, app).await.unwrap();
+  app.users.update_one(doc! { "_id": user._id }, doc! { "$set": { "login_attempts": 0 } }).await.unwrap();
+
+  Ok(json!({
+    "token": format!("{}{}", token, session._id),
+    "new_account": new_account
+  }))
+}
+
+async fn link( headers: &HeaderMap, app: &Arc<AppHandler>, provider: &UpstreamProvider, state: &UpstreamState, upstream_user: &UpstreamUser ) -> Result<Value, APIError>{
+  let cookies = headers.get("cookie");
+  if cookies.is_none() { return Err(APIError::default(headers)) }
+
+  let cookies = cookies.unwrap().to_str().unwrap().to_owned();
+  let cookies = cookies::parse(cookies);
+
+  let token = cookies.get("token").unwrap().clone();
+
+  let identity = token::identify(token, app.clone(), get_ip_from_request(headers).unwrap()).await;
+  if identity.is_err() { return Err(APIError::new(500, identity.unwrap_err().to_string(), headers)) }
+
+  let ( user, session ) = identity.unwrap();
+  if let Err(procedure) = token::verified(&user, &session) { return Ok(procedure) }
+
+  // The link has to finish in the same account that started it
+  if state.user_id != Some(user._id){ return Err(APIError::new(400, "Invalid State".into(), headers)) }
+
+  if user.linked_identities.iter().any(| x | x.provider == provider.id){
+    return Err(APIError::new(400, format!("{} is already linked", provider.name), headers)) }
+
+  if upstream::find_user(app, &provider.id, &upstream_user.subject).await.is_some(){
+    return Err(APIError::new(409, format!("That {} account is linked to someone else", provider.name), headers)) }
+
+  app.users.update_one(doc! { "_id": user._id }, doc! {
+    "$push": { "linked_identities": bson::to_bson(&upstream_user.identity(provider)).unwrap() }
+  }).await.unwrap();
+
+  Ok(json!({
+    "provider": provider.id,
+    "linked": true
+  }))
+}
+
+async fn reauthenticate( headers: &HeaderMap, app: &Arc<AppHandler>, provider: &UpstreamProvider, state: &UpstreamState, upstream_user: &UpstreamUser ) -> Result<Value, APIError>{
+  let cookies = headers.get("cookie");
+  if cookies.is_none() { return Err(APIError::default(headers)) }
+
+  let cookies = cookies.unwrap().to_str().unwrap().to_owned();
+  let cookies = cookies::parse(cookies);
+
+  let token = cookies.get("token").unwrap().clone();
+
+  let identity = token::identify(token, app.clone(), get_ip_from_request(headers).unwrap()).await;
+  if identity.is_err() { return Err(APIError::new(500, identity.unwrap_err().to_string(), headers)) }
+
+  let ( user, session ) = identity.unwrap();
+  if let Err(procedure) = token::verified(&user, &session) { return Ok(procedure) }
+
+  // Only elevates the session that asked for it
+  if state.user_id != Some(user._id) || state.session_id != Some(session._id){ return Err(APIError::new(400, "Invalid State".into(), headers)) }
+
+  if !user.linked_identities.iter().any(| x | x.provider == provider.id && x.subject == upstream_user.subject){
+    return Err(APIError::new(400, format!("That isn't the {} account linked to yours", provider.name), headers)) }
+
+  let elevated_until = Utc::now().timestamp() + token::ELEVATION_LIFETIME;
+  app.sessions.update_one(doc! { "_id": session._id }, doc! { "$set": { "elevated_until": elevated_until } }).await.unwrap();
+
+  Ok(json!({
+    "ok": true,
+    "elevated_until": elevated_until
+  }))
+}
\ No newline at end of file
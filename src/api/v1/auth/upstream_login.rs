@@ -0,0 +1,35 @@
+use std::sync::Arc;
+
+use axum::{ extract::Query, http::{ header, HeaderMap, StatusCode }, response::IntoResponse, Extension, Json };
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{ apphandler::AppHandler, structs::apierror::APIError, util::{ cors::cors, upstream } };
+
+#[derive(Deserialize)]
+pub struct UpstreamLoginRequestQuery{
+  pub provider: String
+}
+
+pub async fn get(
+  headers: HeaderMap,
+  Query(query): Query<UpstreamLoginRequestQuery>,
+  Extension(app): Extension<Arc<AppHandler>>
+) -> impl IntoResponse{
+  let provider = upstream::find(&app, &query.provider);
+  if provider.is_none(){ return Err(APIError::new(404, "Unknown Provider".into(), &headers)) }
+
+  let redirect = upstream::start(&app, provider.unwrap(), "login", None, None).await;
+
+  Ok((
+    StatusCode::OK,
+    [
+      ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+      ( header::ACCESS_CONTROL_ALLOW_METHODS, "GET".into() ),
+      ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+    ],
+    Json(json!({
+      "redirect": redirect
+    }))
+  ))
+}
\ No newline at end of file
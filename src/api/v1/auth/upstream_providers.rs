@@ -0,0 +1,25 @@
+use std::sync::Arc;
+
+use axum::{ http::{ header, HeaderMap, StatusCode }, response::IntoResponse, Extension, Json };
+use serde_json::json;
+
+use crate::{ apphandler::AppHandler, util::cors::cors };
+
+pub async fn get(
+  headers: HeaderMap,
+  Extension(app): Extension<Arc<AppHandler>>
+) -> impl IntoResponse{
+  let providers: Vec<_> = app.upstream_providers.iter()
+    .map(| x | json!({ "id": x.id, "name": x.name }))
+    .collect();
+
+  (
+    StatusCode::OK,
+    [
+      ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+      ( header::ACCESS_CONTROL_ALLOW_METHODS, "GET".into() ),
+      ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+    ],
+    Json(json!(providers))
+  )
+}
\ No newline at end of file
@@ -7,7 +7,7 @@ use rand::{distributions::Alphanumeric, rngs::OsRng, Rng};
 use serde::Deserialize;
 use serde_json::json;
 
-use crate::{ apphandler::AppHandler, structs::{ apierror::APIError, oauthapp::OAuthApplication }, util::{ cookies, cors::cors, ip::get_ip_from_request, token } };
+use crate::{ apphandler::AppHandler, structs::{ apierror::APIError, oauthapp::OAuthApplication }, util::{ cookies, cors::cors, ip::get_ip_from_request, redirect_uri, token } };
 
 #[derive(Deserialize)]
 pub struct AppApplicationRequest{
@@ -47,6 +47,10 @@ pub async fn put(
   }
 
   if !user.roles.contains(&"DEV".to_string()){ return Err(APIError::new(404, "nothing to see here".into(), &headers)) }
+
+  for uri in &body.redirect_uris {
+    if !redirect_uri::is_valid(uri){ return Err(APIError::new(400, format!("Invalid Redirect URI: {}", uri), &headers)) } }
+
   let token: String = rand::thread_rng().sample_iter(&Alphanumeric).take(64).map(char::from).collect();
 
   let argon2 = Argon2::default();
@@ -58,7 +62,22 @@ pub async fn put(
     allow_skip: false,
     key: argon2.hash_password(token.as_bytes(), &salt).unwrap().to_string(),
     redirect_uris: body.redirect_uris,
-    owner_id: user._id
+    owner_id: user._id,
+
+    webhook_url: None,
+    webhook_secret: None,
+
+    jwt_access_tokens: false,
+
+    description: None,
+    homepage_url: None,
+    privacy_policy_url: None,
+    terms_url: None,
+    logo: None,
+
+    verified: false,
+    suspended: false,
+    suspended_reason: None
   };
 
   app.oauth_apps.insert_one(&oapp).await.unwrap();
@@ -0,0 +1,76 @@
+use std::sync::Arc;
+
+use axum::{ extract::Query, http::{ header, HeaderMap, StatusCode }, response::IntoResponse, Extension, Json };
+use bson::doc;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{ apphandler::AppHandler, structs::apierror::APIError, util::{ cookies, cors::cors, ip::get_ip_from_request, token } };
+
+#[derive(Deserialize)]
+pub struct DeleteAppRequestQuery{
+  pub id: String
+}
+
+pub async fn del(
+  headers: HeaderMap,
+  Query(query): Query<DeleteAppRequestQuery>,
+  Extension(app): Extension<Arc<AppHandler>>
+) -> impl IntoResponse{
+  let cookies = headers.get("cookie");
+  if cookies.is_none() { return Err(APIError::default(&headers)) }
+
+  let cookies = cookies.unwrap().to_str().unwrap().to_owned();
+  let cookies = cookies::parse(cookies);
+
+  let token = cookies.get("token").unwrap().clone();
+
+  let identity = token::identify(token, app.clone(), get_ip_from_request(&headers).unwrap()).await;
+  if identity.is_err() { return Err(APIError::new(500, identity.unwrap_err().to_string(), &headers)) }
+
+  let ( user, session ) = identity.unwrap();
+  let verified = token::verified(&user, &session);
+
+  if verified.is_err() {
+    return Ok((
+      StatusCode::OK,
+      [
+        ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+        ( header::ACCESS_CONTROL_ALLOW_METHODS, "GET".into() ),
+        ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+      ],
+      Json(verified.unwrap_err())
+    ))
+  }
+
+  if !user.roles.contains(&"DEV".to_string()){ return Err(APIError::new(404, "nothing to see here".into(), &headers)) }
+
+  let Ok(oauth_app) = token::identify_owned_app(&query.id, &user, &app).await else {
+    return Err(APIError::new(404, "App not found".into(), &headers)) };
+
+  app.oauth_apps.delete_one(doc! { "_id": oauth_app._id }).await.unwrap();
+
+  app.oauth_sessions.delete_many(doc! { "app_id": oauth_app._id }).await.unwrap();
+  app.oauth_codes.delete_many(doc! { "app": oauth_app._id }).await.unwrap();
+  app.oauth_device_codes.delete_many(doc! { "app": oauth_app._id }).await.unwrap();
+  app.oauth_consents.delete_many(doc! { "app_id": oauth_app._id }).await.unwrap();
+
+  if let Some(logo) = &oauth_app.logo {
+    let _ = app.r2().delete_file(format!("/id/app_logos/{}/{}.png", oauth_app._id, logo)).await; }
+
+  app.users.update_many(doc! { "$or": [ { "allowed_apps": oauth_app._id }, { "apps_to_delete_data": oauth_app._id } ] }, doc! {
+    "$pull": { "allowed_apps": oauth_app._id, "apps_to_delete_data": oauth_app._id }
+  }).await.unwrap();
+
+  Ok((
+    StatusCode::OK,
+    [
+      ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+      ( header::ACCESS_CONTROL_ALLOW_METHODS, "GET".into() ),
+      ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+    ],
+    Json(json!({
+      "ok": true
+    }))
+  ))
+}
\ No newline at end of file
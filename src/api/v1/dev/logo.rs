@@ -0,0 +1,75 @@
+use std::sync::Arc;
+
+use axum::{ extract::{ Multipart, Query }, http::{ header, HeaderMap, StatusCode }, response::IntoResponse, Extension, Json };
+use bson::doc;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{ apphandler::AppHandler, structs::apierror::APIError, util::{ avatar, cookies, cors::cors, ip::get_ip_from_request, token } };
+
+#[derive(Deserialize)]
+pub struct AppLogoRequestQuery{
+  pub id: String
+}
+
+pub async fn put(
+  headers: HeaderMap,
+  Query(query): Query<AppLogoRequestQuery>,
+  Extension(app): Extension<Arc<AppHandler>>,
+  mut multipart: Multipart
+) -> impl IntoResponse{
+  let cookies = headers.get("cookie");
+  if cookies.is_none() { return Err(APIError::default(&headers)) }
+
+  let cookies = cookies.unwrap().to_str().unwrap().to_owned();
+  let cookies = cookies::parse(cookies);
+
+  let token = cookies.get("token").unwrap().clone();
+
+  let identity = token::identify(token, app.clone(), get_ip_from_request(&headers).unwrap()).await;
+  if identity.is_err() { return Err(APIError::new(500, identity.unwrap_err().to_string(), &headers)) }
+
+  let ( user, session ) = identity.unwrap();
+  let verified = token::verified(&user, &session);
+
+  if verified.is_err() {
+    return Ok((
+      StatusCode::OK,
+      [
+        ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+        ( header::ACCESS_CONTROL_ALLOW_METHODS, "GET".into() ),
+        ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+      ],
+      Json(verified.unwrap_err())
+    ))
+  }
+
+  if !user.roles.contains(&"DEV".to_string()){ return Err(APIError::new(404, "nothing to see here".into(), &headers)) }
+
+  let Ok(oauth_app) = token::identify_owned_app(&query.id, &user, &app).await else {
+    return Err(APIError::new(404, "App not found".into(), &headers)) };
+
+  let Ok(Some(file)) = multipart.next_field().await else { return Err(APIError::default(&headers)) };
+  if file.content_type() != Some("image/png") { return Err(APIError::default(&headers)) }
+
+  let res = avatar::upload_logo(oauth_app._id, oauth_app.logo, file, app.r2()).await;
+  if res.is_err() { return Err(APIError::new(500, "Could not upload logo".into(), &headers)) }
+
+  let logo = res.unwrap();
+
+  // A new logo needs another review before the app gets its verified badge back
+  app.oauth_apps.update_one(doc! { "_id": oauth_app._id }, doc! { "$set": { "logo": &logo, "verified": false } }).await.unwrap();
+
+  Ok((
+    StatusCode::OK,
+    [
+      ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+      ( header::ACCESS_CONTROL_ALLOW_METHODS, "GET".into() ),
+      ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+    ],
+    Json(json!({
+      "_id": oauth_app._id,
+      "logo": logo
+    }))
+  ))
+}
\ No newline at end of file
@@ -1 +1,8 @@
-pub mod add_app;
\ No newline at end of file
+pub mod add_app;
+pub mod apps;
+pub mod app;
+pub mod update_app;
+pub mod rotate_key;
+pub mod delete_app;
+pub mod webhook;
+pub mod logo;
\ No newline at end of file
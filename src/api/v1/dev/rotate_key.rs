@@ -0,0 +1,78 @@
+use std::sync::Arc;
+
+use argon2::{ password_hash::SaltString, Argon2, PasswordHasher };
+use axum::{ http::{ header, HeaderMap, StatusCode }, response::IntoResponse, Extension, Json };
+use bson::{ doc, oid::ObjectId };
+use rand::{ distributions::Alphanumeric, rngs::OsRng, Rng };
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{ apphandler::AppHandler, structs::apierror::APIError, util::{ cookies, cors::cors, ip::get_ip_from_request, token } };
+
+#[derive(Deserialize)]
+pub struct RotateKeyRequest{
+  pub id: String
+}
+
+pub async fn put(
+  headers: HeaderMap,
+  Extension(app): Extension<Arc<AppHandler>>,
+  Json(body): Json<RotateKeyRequest>
+) -> impl IntoResponse{
+  let cookies = headers.get("cookie");
+  if cookies.is_none() { return Err(APIError::default(&headers)) }
+
+  let cookies = cookies.unwrap().to_str().unwrap().to_owned();
+  let cookies = cookies::parse(cookies);
+
+  let token = cookies.get("token").unwrap().clone();
+
+  let identity = token::identify(token, app.clone(), get_ip_from_request(&headers).unwrap()).await;
+  if identity.is_err() { return Err(APIError::new(500, identity.unwrap_err().to_string(), &headers)) }
+
+  let ( user, session ) = identity.unwrap();
+  let verified = token::verified(&user, &session);
+
+  if verified.is_err() {
+    return Ok((
+      StatusCode::OK,
+      [
+        ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+        ( header::ACCESS_CONTROL_ALLOW_METHODS, "GET".into() ),
+        ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+      ],
+      Json(verified.unwrap_err())
+    ))
+  }
+
+  if !user.roles.contains(&"DEV".to_string()){ return Err(APIError::new(404, "nothing to see here".into(), &headers)) }
+
+  let Ok(oauth_app) = token::identify_owned_app(&body.id, &user, &app).await else {
+    return Err(APIError::new(404, "App not found".into(), &headers)) };
+
+  let token: String = rand::thread_rng().sample_iter(&Alphanumeric).take(64).map(char::from).collect();
+
+  let argon2 = Argon2::default();
+  let salt = SaltString::generate(&mut OsRng);
+
+  // The old key stops working straight away
+  app.oauth_apps.update_one(doc! { "_id": oauth_app._id }, doc! {
+    "$set": { "key": argon2.hash_password(token.as_bytes(), &salt).unwrap().to_string() }
+  }).await.unwrap();
+
+  // App only tokens were handed out with the old key, so they go too
+  app.oauth_sessions.delete_many(doc! { "app_id": oauth_app._id, "user_id": None::<ObjectId> }).await.unwrap();
+
+  Ok((
+    StatusCode::OK,
+    [
+      ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+      ( header::ACCESS_CONTROL_ALLOW_METHODS, "GET".into() ),
+      ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+    ],
+    Json(json!({
+      "_id": oauth_app._id,
+      "key": token
+    }))
+  ))
+}
\ No newline at end of file
@@ -0,0 +1,132 @@
+use std::sync::Arc;
+
+use axum::{ http::{ header, HeaderMap, StatusCode }, response::IntoResponse, Extension, Json };
+use bson::doc;
+use serde::Deserialize;
+use serde_json::json;
+use url::Url;
+
+use crate::{ apphandler::AppHandler, structs::{ apierror::APIError, oauthapp::PublicOAuthApplication }, util::{ cookies, cors::cors, ip::get_ip_from_request, redirect_uri, token } };
+
+#[derive(Deserialize)]
+pub struct UpdateAppRequest{
+  pub id: String,
+  pub name: Option<String>,
+  pub redirect_uris: Option<Vec<String>>,
+  pub jwt_access_tokens: Option<bool>,
+
+  // Sending an empty string clears the field
+  pub description: Option<String>,
+  pub homepage_url: Option<String>,
+  pub privacy_policy_url: Option<String>,
+  pub terms_url: Option<String>
+}
+
+fn branding_url( url: String ) -> Result<Option<String>, ()>{
+  if url.is_empty(){ return Ok(None) }
+
+  match Url::parse(&url) {
+    Ok(parsed) if parsed.scheme() == "https" && parsed.host_str().is_some() => Ok(Some(url)),
+    _ => Err(())
+  }
+}
+
+pub async fn put(
+  headers: HeaderMap,
+  Extension(app): Extension<Arc<AppHandler>>,
+  Json(body): Json<UpdateAppRequest>
+) -> impl IntoResponse{
+  let cookies = headers.get("cookie");
+  if cookies.is_none() { return Err(APIError::default(&headers)) }
+
+  let cookies = cookies.unwrap().to_str().unwrap().to_owned();
+  let cookies = cookies::parse(cookies);
+
+  let token = cookies.get("token").unwrap().clone();
+
+  let identity = token::identify(token, app.clone(), get_ip_from_request(&headers).unwrap()).await;
+  if identity.is_err() { return Err(APIError::new(500, identity.unwrap_err().to_string(), &headers)) }
+
+  let ( user, session ) = identity.unwrap();
+  let verified = token::verified(&user, &session);
+
+  if verified.is_err() {
+    return Ok((
+      StatusCode::OK,
+      [
+        ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+        ( header::ACCESS_CONTROL_ALLOW_METHODS, "GET".into() ),
+        ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+      ],
+      Json(verified.unwrap_err())
+    ))
+  }
+
+  if !user.roles.contains(&"DEV".to_string()){ return Err(APIError::new(404, "nothing to see here".into(), &headers)) }
+
+  let Ok(mut oauth_app) = token::identify_owned_app(&body.id, &user, &app).await else {
+    return Err(APIError::new(404, "App not found".into(), &headers)) };
+
+  if let Some(name) = body.name {
+    if name.trim().is_empty(){ return Err(APIError::new(400, "Invalid Name".into(), &headers)) }
+
+    // The badge vouches for what was reviewed, anything that changes how the app looks or where it sends people needs another review
+    if name != oauth_app.name { oauth_app.verified = false; }
+    oauth_app.name = name;
+
+    // Sessions keep a copy of the name for the account page
+    app.oauth_sessions.update_many(doc! { "app_id": oauth_app._id }, doc! { "$set": { "app_name": &oauth_app.name } }).await.unwrap();
+  }
+
+  if let Some(redirect_uris) = body.redirect_uris {
+    for uri in &redirect_uris {
+      if !redirect_uri::is_valid(uri){ return Err(APIError::new(400, format!("Invalid Redirect URI: {}", uri), &headers)) } }
+
+    if redirect_uris != oauth_app.redirect_uris { oauth_app.verified = false; }
+    oauth_app.redirect_uris = redirect_uris;
+  }
+
+  if let Some(jwt_access_tokens) = body.jwt_access_tokens {
+    oauth_app.jwt_access_tokens = jwt_access_tokens;
+  }
+
+  if let Some(description) = body.description {
+    if description.chars().count() > 500 { return Err(APIError::new(400, "Description is too long".into(), &headers)) }
+    oauth_app.description = if description.trim().is_empty() { None } else { Some(description) };
+  }
+
+  if let Some(url) = body.homepage_url {
+    let Ok(url) = branding_url(url) else { return Err(APIError::new(400, "Invalid Homepage URL".into(), &headers)) };
+    oauth_app.homepage_url = url;
+  }
+
+  if let Some(url) = body.privacy_policy_url {
+    let Ok(url) = branding_url(url) else { return Err(APIError::new(400, "Invalid Privacy Policy URL".into(), &headers)) };
+    oauth_app.privacy_policy_url = url;
+  }
+
+  if let Some(url) = body.terms_url {
+    let Ok(url) = branding_url(url) else { return Err(APIError::new(400, "Invalid Terms URL".into(), &headers)) };
+    oauth_app.terms_url = url;
+  }
+
+  app.oauth_apps.update_one(doc! { "_id": oauth_app._id }, doc! {
+    "$set": { "name": &oauth_app.name, "redirect_uris": &oauth_app.redirect_uris, "jwt_access_tokens": oauth_app.jwt_access_tokens,
+      "description": &oauth_app.description,
+      "homepage_url": &oauth_app.homepage_url,
+      "privacy_policy_url": &oauth_app.privacy_policy_url,
+      "terms_url": &oauth_app.terms_url,
+      "verified": oauth_app.verified
+    }
+  }).await.unwrap();
+
+  Ok((
+    StatusCode::OK,
+    [
+      ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+      ( header::ACCESS_CONTROL_ALLOW_METHODS, "GET".into() ),
+      ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+    ],
+    Json(json!(PublicOAuthApplication::from_app(oauth_app)))
+  ))
+}
\ No newline at end of file
@@ -0,0 +1,97 @@
+use std::sync::Arc;
+
+use axum::{ http::{ header, HeaderMap, StatusCode }, response::IntoResponse, Extension, Json };
+use bson::doc;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{ apphandler::AppHandler, structs::apierror::APIError, util::{ cookies, cors::cors, ip::get_ip_from_request, token, webhook } };
+
+#[derive(Deserialize)]
+pub struct WebhookRequest{
+  pub id: String,
+  pub url: Option<String>
+}
+
+pub async fn put(
+  headers: HeaderMap,
+  Extension(app): Extension<Arc<AppHandler>>,
+  Json(body): Json<WebhookRequest>
+) -> impl IntoResponse{
+  let cookies = headers.get("cookie");
+  if cookies.is_none() { return Err(APIError::default(&headers)) }
+
+  let cookies = cookies.unwrap().to_str().unwrap().to_owned();
+  let cookies = cookies::parse(cookies);
+
+  let token = cookies.get("token").unwrap().clone();
+
+  let identity = token::identify(token, app.clone(), get_ip_from_request(&headers).unwrap()).await;
+  if identity.is_err() { return Err(APIError::new(500, identity.unwrap_err().to_string(), &headers)) }
+
+  let ( user, session ) = identity.unwrap();
+  let verified = token::verified(&user, &session);
+
+  if verified.is_err() {
+    return Ok((
+      StatusCode::OK,
+      [
+        ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+        ( header::ACCESS_CONTROL_ALLOW_METHODS, "GET".into() ),
+        ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+      ],
+      Json(verified.unwrap_err())
+    ))
+  }
+
+  if !user.roles.contains(&"DEV".to_string()){ return Err(APIError::new(404, "nothing to see here".into(), &headers)) }
+
+  let Ok(oauth_app) = token::identify_owned_app(&body.id, &user, &app).await else {
+    return Err(APIError::new(404, "App not found".into(), &headers)) };
+
+  // Sending no URL turns webhooks off
+  let Some(url) = body.url else {
+    app.oauth_apps.update_one(doc! { "_id": oauth_app._id }, doc! {
+      "$set": { "webhook_url": None::<String>, "webhook_secret": None::<String> }
+    }).await.unwrap();
+
+    return Ok((
+      StatusCode::OK,
+      [
+        ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+        ( header::ACCESS_CONTROL_ALLOW_METHODS, "GET".into() ),
+        ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+      ],
+      Json(json!({
+        "_id": oauth_app._id,
+        "webhook_url": None::<String>
+      }))
+    ))
+  };
+
+  let url = webhook::check_url(&url);
+  if url.is_err(){ return Err(APIError::new(400, url.unwrap_err().to_string(), &headers)) }
+
+  let url = url.unwrap().to_string();
+
+  // A new secret every time the URL is set, it's only shown here
+  let secret = webhook::generate_secret();
+
+  app.oauth_apps.update_one(doc! { "_id": oauth_app._id }, doc! {
+    "$set": { "webhook_url": &url, "webhook_secret": &secret }
+  }).await.unwrap();
+
+  Ok((
+    StatusCode::OK,
+    [
+      ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+      ( header::ACCESS_CONTROL_ALLOW_METHODS, "GET".into() ),
+      ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+    ],
+    Json(json!({
+      "_id": oauth_app._id,
+      "webhook_url": url,
+      "webhook_secret": secret
+    }))
+  ))
+}
\ No newline at end of file
@@ -6,4 +6,5 @@ pub mod profile;
 pub mod account;
 pub mod oauth;
 pub mod dev;
+pub mod admin;
 pub mod patreon;
\ No newline at end of file
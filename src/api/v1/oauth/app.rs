@@ -4,12 +4,13 @@ use axum::{ extract::Query, http::{ header, HeaderMap, StatusCode }, response::I
 use bson::{doc, oid::ObjectId};
 use serde_json::json;
 
-use crate::{ apphandler::AppHandler, structs::apierror::APIError, util::{ cookies, cors::cors, ip::get_ip_from_request, token } };
+use crate::{ apphandler::AppHandler, structs::apierror::APIError, util::{ consent, cookies, cors::cors, ip::get_ip_from_request, redirect_uri, scopes, token } };
 
 #[derive(serde::Deserialize, Debug)]
 pub struct OAuthApplicationRequestQuery{
   pub client_id: String,
-  pub redirect_uri: String
+  pub redirect_uri: String,
+  pub scope: Option<String>
 }
 
 pub async fn get( 
@@ -43,7 +44,9 @@ pub async fn get(
     ))
   }
 
-  let oauth_app = app.oauth_apps.find_one(doc! { "_id": ObjectId::parse_str(query.client_id).unwrap() }).await.unwrap();
+  let Ok(client_id) = ObjectId::parse_str(&query.client_id) else { return Err(APIError::new(400, "Invalid App".into(), &headers)) };
+
+  let oauth_app = app.oauth_apps.find_one(doc! { "_id": client_id }).await.unwrap();
   if oauth_app.is_none(){
     return Ok((
       StatusCode::OK,
@@ -62,8 +65,59 @@ pub async fn get(
   }
 
   let oauth_app = oauth_app.unwrap();
+  if oauth_app.suspended{
+    return Ok((
+      StatusCode::OK,
+      [
+        ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+        ( header::ACCESS_CONTROL_ALLOW_METHODS, "GET".into() ),
+        ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+      ],
+      Json(json!({
+        "name": "Invalid App",
+
+        "valid": false,
+        "error": "App is suspended"
+      }))
+    ))
+  }
+
+  let granted_scopes = consent::granted_scopes(&app, user._id, oauth_app._id).await;
+
+  let mut requested_scopes = vec![];
+  let mut new_scopes = vec![];
+
+  // No scopes can't count as already agreed to, otherwise leaving the parameter off skips the consent screen
+  let scope = query.scope.filter(| x | !scopes::parse(x).is_empty()).unwrap_or(scopes::DEFAULT_SCOPE.into());
+
+  for scope in scopes::parse(&scope) {
+    let description = scopes::describe(&scope);
+
+    if description.is_none(){
+      return Ok((
+        StatusCode::OK,
+        [
+          ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+          ( header::ACCESS_CONTROL_ALLOW_METHODS, "GET".into() ),
+          ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+        ],
+        Json(json!({
+          "name": "Invalid App",
+
+          "valid": false,
+          "error": "Invalid scopes"
+        }))
+      ))
+    }
+
+    // Scopes the user hasn't agreed to before get pointed out on the consent screen
+    let granted = granted_scopes.contains(&scope);
+    if !granted { new_scopes.push(scope.clone()); }
+
+    requested_scopes.push(json!({ "scope": scope, "description": description.unwrap(), "granted": granted }));
+  }
 
-  if oauth_app.redirect_uris.contains(&query.redirect_uri){
+  if redirect_uri::is_allowed(&oauth_app.redirect_uris, &query.redirect_uri){
     Ok((
       StatusCode::OK,
       [
@@ -73,7 +127,16 @@ pub async fn get(
       ],
       Json(json!({
         "name": oauth_app.name,
-        "allow_skip": oauth_app.allow_skip,
+        "id": oauth_app._id.to_hex(),
+        "description": oauth_app.description,
+        "homepage_url": oauth_app.homepage_url,
+        "privacy_policy_url": oauth_app.privacy_policy_url,
+        "terms_url": oauth_app.terms_url,
+        "logo": oauth_app.logo,
+        "verified": oauth_app.verified,
+        "allow_skip": oauth_app.allow_skip || new_scopes.is_empty(),
+        "scopes": requested_scopes,
+        "new_scopes": new_scopes,
 
         "valid": true,
         "error": "None"
@@ -1,29 +1,62 @@
 use std::{env, sync::Arc};
 
 use argon2::{ password_hash::SaltString, Argon2, PasswordHasher };
-use axum::{ extract::Query, http::{ header, HeaderMap, StatusCode }, response::IntoResponse, Extension, Json };
+use axum::{ extract::Query, http::{ header, HeaderMap, HeaderName, StatusCode }, response::IntoResponse, Extension, Json };
 use bson::{doc, oid::ObjectId};
 use chrono::Utc;
 use rand::{distributions::Alphanumeric, rngs::OsRng, Rng};
 use serde::Deserialize;
-use serde_json::json;
+use serde_json::{ json, Value };
 
-use crate::{ apphandler::AppHandler, structs::{apierror::APIError, oauthcode::OAuthCode, tunnel::TurnstileRes}, util::{ cookies, cors::cors, ip::get_ip_from_request, token } };
+use crate::{ apphandler::AppHandler, structs::{apierror::APIError, oauthcode::OAuthCode, tunnel::TurnstileRes}, util::{ consent, cookies, cors::cors, ip::get_ip_from_request, oidc::ISSUER, pkce, redirect_uri, scopes, token } };
 
 #[derive(serde::Deserialize, Debug)]
 pub struct OAuthApplicationRequestQuery{
   pub response_type: String,
   pub client_id: String,
   pub redirect_uri: String,
-  pub scope: String
+  pub scope: Option<String>,
+
+  pub code_challenge: Option<String>,
+  pub code_challenge_method: Option<String>,
+
+  pub nonce: Option<String>,
+  pub state: Option<String>,
+
+  // Respond with the full URL to send the user back to, instead of just the code
+  #[serde(default)]
+  pub redirect: bool
 }
 
 #[derive(Deserialize)]
 pub struct OAuthApplicationRequest{
-  pub token: String
+  pub token: String,
+
+  #[serde(default)]
+  pub deny: bool
 }
 
-const SCOPES: [ &'static str; 1 ] = [ "identify" ];
+type AuthorizeResponse = ( StatusCode, [ ( HeaderName, String ); 3 ], Json<Value> );
+
+// Once the app and redirect URI are known to be good, errors go back to the app (RFC 6749 4.1.2.1)
+fn client_error( headers: &HeaderMap, query: &OAuthApplicationRequestQuery, error: &str, description: &str ) -> Result<AuthorizeResponse, APIError>{
+  if !query.redirect { return Err(APIError::new(400, description.into(), headers)) }
+
+  let mut params = vec![ ( "error", error ), ( "error_description", description ), ( "iss", ISSUER ) ];
+  if let Some(state) = &query.state { params.push(( "state", state )); }
+
+  Ok((
+    StatusCode::OK,
+    [
+      ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(headers) ),
+      ( header::ACCESS_CONTROL_ALLOW_METHODS, "GET".into() ),
+      ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+    ],
+    Json(json!({
+      "redirect": redirect_uri::with_params(&query.redirect_uri, &params)
+    }))
+  ))
+}
 
 pub async fn put(
   headers: HeaderMap,
@@ -57,16 +90,33 @@ pub async fn put(
     ))
   }
 
-  if query.response_type != "code" && query.response_type != "code_skip" { return Err(APIError::new(400, "Invalid Response Type.".into(), &headers)); }
+  let Ok(client_id) = ObjectId::parse_str(&query.client_id) else { return Err(APIError::new(500, "Invalid App".into(), &headers)) };
 
-  let oauth_app = app.oauth_apps.find_one(doc! { "_id": ObjectId::parse_str(query.client_id).unwrap() }).await.unwrap();
+  let oauth_app = app.oauth_apps.find_one(doc! { "_id": client_id }).await.unwrap();
   if oauth_app.is_none(){ return Err(APIError::new(500, "Invalid App".into(), &headers)) }
 
+  // Never redirect to a URI we haven't checked, these errors stay with us
   let oauth_app = oauth_app.unwrap();
-  if !oauth_app.redirect_uris.contains(&query.redirect_uri){ return Err(APIError::new(500, "Invalid Redirect URI".into(), &headers)) }
+  if oauth_app.suspended { return Err(APIError::new(403, "App is suspended".into(), &headers)) }
+
+  if !redirect_uri::is_allowed(&oauth_app.redirect_uris, &query.redirect_uri){ return Err(APIError::new(500, "Invalid Redirect URI".into(), &headers)) }
 
+  if query.response_type != "code" && query.response_type != "code_skip" {
+    return client_error(&headers, &query, "unsupported_response_type", "Invalid Response Type.") }
+
+  if body.deny { return client_error(&headers, &query, "access_denied", "The user denied the request.") }
+
+  // Same fallback as the consent screen, so what the user approved is what gets granted
+  let scope = query.scope.clone().filter(| x | !scopes::parse(x).is_empty()).unwrap_or(scopes::DEFAULT_SCOPE.into());
+  let scopes = scopes::parse(&scope);
+
+  for scope in &scopes {
+    if !scopes::is_valid(scope){ return client_error(&headers, &query, "invalid_scope", "Invalid Scopes") } }
+
+  // Skipping is only allowed for trusted apps, or when the user already agreed to everything being asked for
   if query.response_type == "code_skip" {
-    if !oauth_app.allow_skip { return Err(APIError::new(400, "Invalid Response Type.".into(), &headers)); }
+    if !oauth_app.allow_skip && !consent::covers(&app, user._id, oauth_app._id, &scopes).await {
+      return client_error(&headers, &query, "consent_required", "The user has not agreed to all the requested scopes.") }
   } else{
     let client = reqwest::Client::new();
     let dat = client.post("https://challenges.cloudflare.com/turnstile/v0/siteverify")
@@ -81,9 +131,17 @@ pub async fn put(
     if !dat.success { return Err(APIError::new(400, "Invalid Captcha.".into(), &headers)); }
   }
 
-  let scopes = query.scope.split(",");
-  for scope in scopes.clone() {
-    if !SCOPES.contains(&scope){ return Err(APIError::new(500, "Invalid Scopes".into(), &headers)) } }
+  let code_challenge_method = if let Some(code_challenge) = &query.code_challenge {
+    // Defaults to "plain" when no method is given (RFC 7636 4.3)
+    let method = query.code_challenge_method.clone().unwrap_or("plain".into());
+
+    if !pkce::METHODS.contains(&method.as_str()){ return client_error(&headers, &query, "invalid_request", "Invalid Code Challenge Method.") }
+    if !pkce::is_valid(code_challenge){ return client_error(&headers, &query, "invalid_request", "Invalid Code Challenge.") }
+
+    Some(method)
+  } else{
+    None
+  };
 
   let token: String = rand::thread_rng().sample_iter(&Alphanumeric).take(64).map(char::from).collect();
 
@@ -97,7 +155,7 @@ pub async fn put(
     token: argon2.hash_password(token.as_bytes(), &salt).unwrap().to_string(),
 
     app: oauth_app._id,
-    redirect_uri: query.redirect_uri,
+    redirect_uri: query.redirect_uri.clone(),
 
     created_on: now,
     expires_on: now + 60, // Expires in 1 minute. (OAuth 2.0 spec says max 10 min)
@@ -105,12 +163,25 @@ pub async fn put(
     refresh: false,
 
     user_id: user._id,
-    scopes: scopes.map(|x| x.into()).collect()
+    scopes: scopes.clone(),
+
+    code_challenge: query.code_challenge,
+    code_challenge_method,
+
+    nonce: query.nonce,
+
+    public_client: false,
+
+    grant_id: None,
+    used: false
   };
 
-  app.oauth_codes.delete_many(doc! { "user_id": user._id, "app": oauth_app._id }).await.unwrap();
+  app.oauth_codes.delete_many(doc! { "user_id": user._id, "app": oauth_app._id, "expires_on": { "$lt": now } }).await.unwrap();
   app.oauth_codes.insert_one(&ocode).await.unwrap();
 
+  // The user went through the consent screen, remember what they agreed to
+  if query.response_type == "code" { consent::grant(&app, user._id, oauth_app._id, &scopes).await; }
+
   if !user.allowed_apps.contains(&oauth_app._id){
     app.users.update_one(doc! { "_id": user._id }, doc! {
       "$push": { "allowed_apps": oauth_app._id }
@@ -124,8 +195,15 @@ pub async fn put(
       ( header::ACCESS_CONTROL_ALLOW_METHODS, "GET".into() ),
       ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
     ],
-    Json(json!({
-      "code": format!("{}{}", ocode._id.to_hex(), token)
-    }))
+    Json(if query.redirect {
+      let code = format!("{}{}", ocode._id.to_hex(), token);
+
+      let mut params = vec![ ( "code", code.as_str() ), ( "iss", ISSUER ) ];
+      if let Some(state) = &query.state { params.push(( "state", state )); }
+
+      json!({ "redirect": redirect_uri::with_params(&query.redirect_uri, &params) })
+    } else{
+      json!({ "code": format!("{}{}", ocode._id.to_hex(), token), "state": query.state })
+    })
   ))
 }
\ No newline at end of file
@@ -0,0 +1,80 @@
+use std::sync::Arc;
+
+use axum::{ extract::Query, http::{ header, HeaderMap, StatusCode }, response::IntoResponse, Extension, Json };
+use bson::doc;
+use serde_json::json;
+
+use crate::{ apphandler::AppHandler, structs::apierror::APIError, util::{ cookies, cors::cors, ip::get_ip_from_request, scopes, token } };
+
+use super::device_code::find_pending;
+
+#[derive(serde::Deserialize, Debug)]
+pub struct OAuthDeviceRequestQuery{
+  pub user_code: String
+}
+
+pub async fn get(
+  headers: HeaderMap,
+  Query(query): Query<OAuthDeviceRequestQuery>,
+  Extension(app): Extension<Arc<AppHandler>>
+) -> impl IntoResponse{
+  let cookies = headers.get("cookie");
+  if cookies.is_none() { return Err(APIError::default(&headers)) }
+
+  let cookies = cookies.unwrap().to_str().unwrap().to_owned();
+  let cookies = cookies::parse(cookies);
+
+  let token = cookies.get("token").unwrap().clone();
+
+  let identity = token::identify(token, app.clone(), get_ip_from_request(&headers).unwrap()).await;
+  if identity.is_err() { return Err(APIError::new(500, identity.unwrap_err().to_string(), &headers)) }
+
+  let ( user, session ) = identity.unwrap();
+  let verified = token::verified(&user, &session);
+
+  if verified.is_err() {
+    return Ok((
+      StatusCode::OK,
+      [
+        ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+        ( header::ACCESS_CONTROL_ALLOW_METHODS, "GET".into() ),
+        ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+      ],
+      Json(verified.unwrap_err())
+    ))
+  }
+
+  let device_code = match find_pending(&app, &get_ip_from_request(&headers).unwrap(), session._id, &query.user_code).await {
+    Ok(device_code) => device_code,
+    Err(retry_at) => return Err(APIError::new(429, format!("Too many attempts, try again at {}", retry_at), &headers))
+  };
+
+  if device_code.is_none(){ return Err(APIError::new(400, "Invalid Code".into(), &headers)) }
+  let device_code = device_code.unwrap();
+
+  let oauth_app = app.oauth_apps.find_one(doc! { "_id": device_code.app }).await.unwrap();
+  if oauth_app.is_none(){ return Err(APIError::new(500, "Invalid App".into(), &headers)) }
+
+  let oauth_app = oauth_app.unwrap();
+  if oauth_app.suspended { return Err(APIError::new(403, "App is suspended".into(), &headers)) }
+
+  let requested_scopes: Vec<_> = device_code.scopes.iter()
+    .map(| x | json!({ "scope": x, "description": scopes::describe(x) }))
+    .collect();
+
+  Ok((
+    StatusCode::OK,
+    [
+      ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+      ( header::ACCESS_CONTROL_ALLOW_METHODS, "GET".into() ),
+      ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+    ],
+    Json(json!({
+      "name": oauth_app.name,
+      "scopes": requested_scopes,
+
+      "valid": true,
+      "error": "None"
+    }))
+  ))
+}
\ No newline at end of file
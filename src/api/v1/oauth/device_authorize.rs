@@ -0,0 +1,104 @@
+use std::{ env, sync::Arc };
+
+use axum::{ http::{ header, HeaderMap, StatusCode }, response::IntoResponse, Extension, Json };
+use bson::doc;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{ apphandler::AppHandler, structs::{ apierror::APIError, tunnel::TurnstileRes }, util::{ consent, cookies, cors::cors, ip::get_ip_from_request, token } };
+
+use super::device_code::find_pending;
+
+#[derive(Deserialize)]
+pub struct OAuthDeviceAuthorizeRequest{
+  pub user_code: String,
+  pub approve: bool,
+  pub token: String
+}
+
+pub async fn put(
+  headers: HeaderMap,
+  Extension(app): Extension<Arc<AppHandler>>,
+  Json(body): Json<OAuthDeviceAuthorizeRequest>
+) -> impl IntoResponse{
+  let cookies = headers.get("cookie");
+  if cookies.is_none() { return Err(APIError::default(&headers)) }
+
+  let cookies = cookies.unwrap().to_str().unwrap().to_owned();
+  let cookies = cookies::parse(cookies);
+
+  let token = cookies.get("token").unwrap().clone();
+
+  let identity = token::identify(token, app.clone(), get_ip_from_request(&headers).unwrap()).await;
+  if identity.is_err() { return Err(APIError::new(500, identity.unwrap_err().to_string(), &headers)) }
+
+  let ( user, session ) = identity.unwrap();
+  let verified = token::verified(&user, &session);
+
+  if verified.is_err() {
+    return Ok((
+      StatusCode::OK,
+      [
+        ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+        ( header::ACCESS_CONTROL_ALLOW_METHODS, "GET".into() ),
+        ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+      ],
+      Json(verified.unwrap_err())
+    ))
+  }
+
+  let client = reqwest::Client::new();
+  let dat = client.post("https://challenges.cloudflare.com/turnstile/v0/siteverify")
+    .body(serde_json::to_string(&json!({
+      "secret": env::var("CF_TURNSTILE_SECRET").unwrap(),
+      "response": body.token
+    })).unwrap())
+    .header("Content-Type", "application/json")
+    .send().await.unwrap().text().await.unwrap();
+
+  let dat: TurnstileRes = serde_json::from_str(&dat).unwrap();
+  if !dat.success { return Err(APIError::new(400, "Invalid Captcha.".into(), &headers)); }
+
+  let device_code = match find_pending(&app, &get_ip_from_request(&headers).unwrap(), session._id, &body.user_code).await {
+    Ok(device_code) => device_code,
+    Err(retry_at) => return Err(APIError::new(429, format!("Too many attempts, try again at {}", retry_at), &headers))
+  };
+
+  if device_code.is_none(){ return Err(APIError::new(400, "Invalid Code".into(), &headers)) }
+  let device_code = device_code.unwrap();
+
+  let oauth_app = app.oauth_apps.find_one(doc! { "_id": device_code.app }).await.unwrap();
+  if oauth_app.is_none(){ return Err(APIError::new(500, "Invalid App".into(), &headers)) }
+
+  if oauth_app.unwrap().suspended { return Err(APIError::new(403, "App is suspended".into(), &headers)) }
+
+  if body.approve {
+    app.oauth_device_codes.update_one(doc! { "_id": device_code._id }, doc! {
+      "$set": { "approved": true, "user_id": user._id }
+    }).await.unwrap();
+
+    consent::grant(&app, user._id, device_code.app, &device_code.scopes).await;
+
+    if !user.allowed_apps.contains(&device_code.app){
+      app.users.update_one(doc! { "_id": user._id }, doc! {
+        "$push": { "allowed_apps": device_code.app }
+      }).await.unwrap();
+    }
+  } else{
+    app.oauth_device_codes.update_one(doc! { "_id": device_code._id }, doc! {
+      "$set": { "denied": true }
+    }).await.unwrap();
+  }
+
+  Ok((
+    StatusCode::OK,
+    [
+      ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+      ( header::ACCESS_CONTROL_ALLOW_METHODS, "GET".into() ),
+      ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+    ],
+    Json(json!({
+      "approved": body.approve
+    }))
+  ))
+}
\ No newline at end of file
@@ -0,0 +1,132 @@
+use std::sync::Arc;
+
+use argon2::{ password_hash::SaltString, Argon2, PasswordHasher };
+use axum::{ http::{ header, HeaderMap, StatusCode }, response::IntoResponse, Extension, Form, Json };
+use bson::{ doc, oid::ObjectId };
+use chrono::Utc;
+use rand::{ distributions::Alphanumeric, rngs::OsRng, seq::SliceRandom, Rng };
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{ apphandler::AppHandler, structs::{ apierror::APIError, oauthdevicecode::OAuthDeviceCode }, util::{ cors::cors, ratelimit, scopes, token } };
+
+const VERIFICATION_URI: &str = "https://id.phazed.xyz/device";
+
+// No vowels so we don't spell anything, no 0/O or 1/I mixups (RFC 8628 6.1)
+const USER_CODE_CHARS: &[u8] = b"BCDFGHJKLMNPQRSTVWXZ";
+
+#[derive(Deserialize)]
+pub struct OAuthDeviceCodeRequest{
+  pub client_id: String,
+  pub scope: Option<String>
+}
+
+pub fn normalize_user_code( user_code: &str ) -> String{
+  user_code.chars().filter(| x | x.is_ascii_alphanumeric()).map(| x | x.to_ascii_uppercase()).collect()
+}
+
+// Looks up a code that's still waiting on the user, wrong guesses count towards the IP and session's limits.
+// Returns when they can try again if they've guessed too many
+pub async fn find_pending( app: &AppHandler, ip: &str, session_id: ObjectId, user_code: &str ) -> Result<Option<OAuthDeviceCode>, i64>{
+  let limits = [
+    ( &ratelimit::USER_CODE_IP, ip.to_owned() ),
+    ( &ratelimit::USER_CODE_SESSION, session_id.to_hex() )
+  ];
+
+  let hits = ratelimit::begin(app, &limits).await?;
+
+  let device_code = app.oauth_device_codes.find_one(doc! {
+    "user_code": normalize_user_code(user_code),
+    "expires_on": { "$gt": Utc::now().timestamp() },
+    "approved": false,
+    "denied": false
+  }).await.unwrap();
+
+  if device_code.is_some(){ ratelimit::forget(app, hits).await; }
+  Ok(device_code)
+}
+
+pub async fn post(
+  headers: HeaderMap,
+  Extension(app): Extension<Arc<AppHandler>>,
+  Form(body): Form<OAuthDeviceCodeRequest>
+) -> impl IntoResponse{
+  let oauth_app = ObjectId::parse_str(&body.client_id);
+  if oauth_app.is_err(){ return Err(APIError::new(500, "Invalid App".into(), &headers)) }
+
+  let oauth_app = app.oauth_apps.find_one(doc! { "_id": oauth_app.unwrap() }).await.unwrap();
+  if oauth_app.is_none(){ return Err(APIError::new(500, "Invalid App".into(), &headers)) }
+
+  let oauth_app = oauth_app.unwrap();
+  if oauth_app.suspended { return Err(APIError::new(403, "App is suspended".into(), &headers)) }
+
+  // Device clients are usually public, but if they send an app key it has to be right
+  if let Some(auth) = headers.get("Authorization"){
+    let auth = auth.to_str().unwrap().to_owned();
+
+    let valid = token::identify_app(auth, &body.client_id, app.clone()).await;
+    if valid.is_err(){ return Err(APIError::new(401, "Invalid App Key".into(), &headers)) }
+  }
+
+  let scope = body.scope.filter(| x | !scopes::parse(x).is_empty()).unwrap_or(scopes::DEFAULT_SCOPE.into());
+  let scopes = scopes::parse(&scope);
+
+  for scope in &scopes {
+    if !scopes::is_valid(scope){ return Err(APIError::new(500, "Invalid Scopes".into(), &headers)) } }
+
+  let now = Utc::now().timestamp();
+  app.oauth_device_codes.delete_many(doc! { "expires_on": { "$lt": now } }).await.unwrap();
+
+  let mut user_code: String;
+  loop {
+    user_code = ( 0..8 ).map(| _ | *USER_CODE_CHARS.choose(&mut rand::thread_rng()).unwrap() as char).collect();
+    if app.oauth_device_codes.find_one(doc! { "user_code": &user_code }).await.unwrap().is_none() { break; }
+  }
+
+  let token: String = rand::thread_rng().sample_iter(&Alphanumeric).take(64).map(char::from).collect();
+
+  let argon2 = Argon2::default();
+  let salt = SaltString::generate(&mut OsRng);
+
+  let device_code = OAuthDeviceCode {
+    _id: ObjectId::new(),
+    token: argon2.hash_password(token.as_bytes(), &salt).unwrap().to_string(),
+
+    user_code,
+
+    app: oauth_app._id,
+    scopes,
+
+    created_on: now,
+    expires_on: now + 600, // Expires in 10 minutes
+
+    interval: 5,
+    last_polled: 0,
+
+    approved: false,
+    denied: false,
+
+    user_id: None
+  };
+
+  app.oauth_device_codes.insert_one(&device_code).await.unwrap();
+
+  let user_code = format!("{}-{}", &device_code.user_code[..4], &device_code.user_code[4..]);
+
+  Ok((
+    StatusCode::OK,
+    [
+      ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+      ( header::ACCESS_CONTROL_ALLOW_METHODS, "POST".into() ),
+      ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+    ],
+    Json(json!({
+      "device_code": format!("{}{}", device_code._id.to_hex(), token),
+      "user_code": user_code,
+      "verification_uri": VERIFICATION_URI,
+      "verification_uri_complete": format!("{}?code={}", VERIFICATION_URI, user_code),
+      "expires_in": 600,
+      "interval": device_code.interval
+    }))
+  ))
+}
\ No newline at end of file
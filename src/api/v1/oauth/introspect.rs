@@ -0,0 +1,76 @@
+use std::sync::Arc;
+
+use axum::{ http::{ header, HeaderMap, StatusCode }, response::IntoResponse, Extension, Form, Json };
+use bson::doc;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{ apphandler::AppHandler, util::{ cors::cors, token } };
+
+#[derive(Deserialize)]
+pub struct OAuthIntrospectRequest{
+  pub client_id: Option<String>,
+  pub client_secret: Option<String>,
+  pub token: String
+}
+
+pub async fn post(
+  headers: HeaderMap,
+  Extension(app): Extension<Arc<AppHandler>>,
+  Form(body): Form<OAuthIntrospectRequest>
+) -> impl IntoResponse{
+  // Same client authentication as the token endpoint (RFC 7662 2.1)
+  let oauth_app = match token::identify_client(&headers, body.client_id, body.client_secret, &app).await {
+    Ok(oauth_app) => oauth_app,
+    Err(err) => return Err(err)
+  };
+
+  // Apps can only introspect their own tokens, anything else is reported as inactive (RFC 7662 2.2)
+  let mut oauth_session = token::identify_oauth_token(&body.token, app.clone()).await.ok().filter(| x | x.app_id == oauth_app._id);
+
+  // JWTs are valid on their own, but introspection is the place to find out the grant was revoked
+  if token::is_jwt(&body.token) {
+    if let Some(session) = &oauth_session {
+      if app.oauth_sessions.find_one(doc! { "_id": session._id }).await.unwrap().is_none(){ oauth_session = None; }
+    }
+  }
+
+  let mut res = json!({ "active": false });
+
+  if let Some(oauth_session) = oauth_session {
+    let user = if oauth_session.user_id.is_some() {
+      app.users.find_one(doc! { "_id": oauth_session.user_id }).await.unwrap()
+    } else{
+      None
+    };
+
+    // App only tokens are still active without a user
+    if user.is_some() || oauth_session.user_id.is_none() {
+      res = json!({
+        "active": true,
+        "scope": oauth_session.scopes.join(" "),
+        "client_id": oauth_session.app_id.to_hex(),
+        "app_id": oauth_session.app_id.to_hex(),
+        "token_type": "Bearer",
+        "iat": oauth_session.created_on,
+        "exp": oauth_session.expires_on
+      });
+    }
+
+    if let Some(user) = user {
+      res["user_id"] = user._id.to_hex().into();
+      res["sub"] = user._id.to_hex().into();
+      res["username"] = user.username.into();
+    }
+  }
+
+  Ok((
+    StatusCode::OK,
+    [
+      ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+      ( header::ACCESS_CONTROL_ALLOW_METHODS, "POST".into() ),
+      ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+    ],
+    Json(res)
+  ))
+}
\ No newline at end of file
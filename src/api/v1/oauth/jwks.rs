@@ -0,0 +1,23 @@
+use std::sync::Arc;
+
+use axum::{ http::{ header, HeaderMap, StatusCode }, response::IntoResponse, Extension, Json };
+
+use crate::{ apphandler::AppHandler, structs::apierror::APIError, util::{ cors::cors, jwt } };
+
+pub async fn get(
+  headers: HeaderMap,
+  Extension(app): Extension<Arc<AppHandler>>
+) -> impl IntoResponse{
+  let jwks = jwt::jwks(&app).await;
+  if jwks.is_err(){ return Err(APIError::default(&headers)) }
+
+  Ok((
+    StatusCode::OK,
+    [
+      ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+      ( header::ACCESS_CONTROL_ALLOW_METHODS, "GET".into() ),
+      ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+    ],
+    Json(jwks.unwrap())
+  ))
+}
\ No newline at end of file
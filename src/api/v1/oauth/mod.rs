@@ -2,4 +2,12 @@ pub mod app;
 pub mod authorize;
 pub mod token;
 pub mod profile;
-pub mod to_delete;
\ No newline at end of file
+pub mod to_delete;
+pub mod jwks;
+pub mod openid_configuration;
+pub mod introspect;
+pub mod revoke;
+pub mod device_code;
+pub mod device;
+pub mod device_authorize;
+pub mod to_delete_ack;
\ No newline at end of file
@@ -0,0 +1,37 @@
+use axum::{ http::{ header, HeaderMap, StatusCode }, response::IntoResponse, Json };
+use serde_json::json;
+
+use crate::util::{ cors::cors, oidc::{ AUTHORIZATION_ENDPOINT, ISSUER }, scopes::SCOPES };
+
+pub async fn get( headers: HeaderMap ) -> impl IntoResponse{
+  (
+    StatusCode::OK,
+    [
+      ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+      ( header::ACCESS_CONTROL_ALLOW_METHODS, "GET".into() ),
+      ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+    ],
+    Json(json!({
+      "issuer": ISSUER,
+      "authorization_endpoint": AUTHORIZATION_ENDPOINT,
+      "token_endpoint": format!("{}/api/v1/oauth/token", ISSUER),
+      "userinfo_endpoint": format!("{}/api/v1/oauth/profile", ISSUER),
+      "jwks_uri": format!("{}/api/v1/oauth/jwks", ISSUER),
+      "introspection_endpoint": format!("{}/api/v1/oauth/introspect", ISSUER),
+      "revocation_endpoint": format!("{}/api/v1/oauth/revoke", ISSUER),
+      "device_authorization_endpoint": format!("{}/api/v1/oauth/device/code", ISSUER),
+
+      "scopes_supported": SCOPES.map(| x | x.0),
+      "response_types_supported": [ "code" ],
+      "grant_types_supported": [ "authorization_code", "refresh_token", "client_credentials", "urn:ietf:params:oauth:grant-type:device_code" ],
+      "subject_types_supported": [ "public" ],
+      "id_token_signing_alg_values_supported": [ "RS256" ],
+      "code_challenge_methods_supported": [ "S256", "plain" ],
+      "token_endpoint_auth_methods_supported": [ "client_secret_basic", "client_secret_post", "none" ],
+      "introspection_endpoint_auth_methods_supported": [ "client_secret_basic", "client_secret_post" ],
+      "revocation_endpoint_auth_methods_supported": [ "client_secret_basic", "client_secret_post" ],
+      "authorization_response_iss_parameter_supported": true,
+      "claims_supported": [ "iss", "sub", "aud", "iat", "exp", "nonce", "preferred_username", "email", "email_verified" ]
+    }))
+  )
+}
\ No newline at end of file
@@ -13,10 +13,33 @@ pub async fn get(
   if auth.is_none() { return Err(APIError::default(&headers)) }
 
   let auth = auth.unwrap().to_str().unwrap();
-  let user = token::identify_oauth(auth.to_string(), "identify".into(), app).await;
+  // This is also the OIDC userinfo endpoint, where openid on its own is enough to get the subject
+  let user = token::identify_oauth(auth.to_string(), &[ "identify", "openid" ], app).await;
 
   if user.is_err(){ return Err(APIError::new(500, user.unwrap_err().to_string(), &headers)) }
-  let user = user.unwrap();
+  let ( user, oauth_session ) = user.unwrap();
+
+  let mut profile = json!({ "sub": user._id.to_hex() });
+
+  if oauth_session.scopes.contains(&"identify".into()){
+    profile["id"] = user._id.to_hex().into();
+    profile["username"] = user.username.into();
+    profile["preferred_username"] = profile["username"].clone();
+  }
+
+  // Only hand out what the user agreed to share with this app
+  if oauth_session.scopes.contains(&"email".into()){
+    profile["email"] = user.email.into();
+    profile["email_verified"] = user.email_verified.into();
+  }
+
+  if oauth_session.scopes.contains(&"avatar".into()){ profile["avatar"] = user.avatar.into(); }
+  if oauth_session.scopes.contains(&"roles".into()){ profile["roles"] = user.roles.into(); }
+
+  if oauth_session.scopes.contains(&"patreon".into()){
+    profile["patreon_linked"] = user.patreon_id.is_some().into();
+    profile["patreon_tiers"] = user.patreon_tiers.into();
+  }
 
   Ok((
     StatusCode::OK,
@@ -25,14 +48,6 @@ pub async fn get(
       ( header::ACCESS_CONTROL_ALLOW_METHODS, "GET".into() ),
       ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
     ],
-    Json(json!({
-      "id": user._id.to_hex(),
-      "username": user.username,
-      "email": user.email,
-      "avatar": user.avatar,
-      "roles": user.roles,
-      "patreon_linked": user.patreon_id.is_some(),
-      "patreon_tiers": user.patreon_tiers
-    }))
+    Json(profile)
   ))
 }
\ No newline at end of file
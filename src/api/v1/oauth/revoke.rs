@@ -0,0 +1,74 @@
+use std::sync::Arc;
+
+use argon2::{ password_hash::Encoding, Argon2, PasswordHash, PasswordVerifier };
+use axum::{ http::{ header, HeaderMap, StatusCode }, response::IntoResponse, Extension, Form, Json };
+use bson::{ doc, oid::ObjectId };
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{ apphandler::AppHandler, util::{ cors::cors, token } };
+
+#[derive(Deserialize)]
+pub struct OAuthRevokeRequest{
+  pub client_id: Option<String>,
+  pub client_secret: Option<String>,
+  pub token: String
+}
+
+pub async fn post(
+  headers: HeaderMap,
+  Extension(app): Extension<Arc<AppHandler>>,
+  Form(body): Form<OAuthRevokeRequest>
+) -> impl IntoResponse{
+  // Same client authentication as the token endpoint (RFC 7009 2.1)
+  let oauth_app = match token::identify_client(&headers, body.client_id, body.client_secret, &app).await {
+    Ok(oauth_app) => oauth_app,
+    Err(err) => return Err(err)
+  };
+
+  // Revoking a JWT access token removes the session it was issued with, the JWT itself runs out on its own
+  if let Ok(oauth_session) = token::identify_jwt(&body.token, &app).await {
+    app.oauth_sessions.delete_one(doc! { "_id": oauth_session._id, "app_id": oauth_app._id }).await.unwrap();
+  }
+
+  // Invalid or unknown tokens still get a 200, the client has nothing to clean up either way (RFC 7009 2.2)
+  if body.token.len() > 24 && body.token.is_char_boundary(24) {
+    let ( token_id, token ) = body.token.split_at(24);
+
+    if let Ok(token_id) = ObjectId::parse_str(token_id) {
+      let argon2 = Argon2::default();
+
+      // Token IDs are unique across both collections, so token_type_hint isn't needed to find it
+      let oauth_session = app.oauth_sessions.find_one(doc! { "_id": token_id, "app_id": oauth_app._id }).await.unwrap();
+      if let Some(oauth_session) = oauth_session {
+        if argon2.verify_password(token.as_bytes(), &PasswordHash::parse(&oauth_session.token, Encoding::B64).unwrap()).is_ok(){
+          app.oauth_sessions.delete_one(doc! { "_id": oauth_session._id }).await.unwrap();
+        }
+      }
+
+      let oauth_code = app.oauth_codes.find_one(doc! { "_id": token_id, "app": oauth_app._id, "refresh": true }).await.unwrap();
+      if let Some(oauth_code) = oauth_code {
+        if argon2.verify_password(token.as_bytes(), &PasswordHash::parse(&oauth_code.token, Encoding::B64).unwrap()).is_ok(){
+          // Revoking a refresh token also revokes the access tokens it was issued with, and the rest of its family
+          app.oauth_codes.delete_one(doc! { "_id": oauth_code._id }).await.unwrap();
+
+          if oauth_code.grant_id.is_some(){
+            app.oauth_codes.delete_many(doc! { "user_id": oauth_code.user_id, "grant_id": oauth_code.grant_id }).await.unwrap();
+          }
+
+          app.oauth_sessions.delete_many(doc! { "user_id": oauth_code.user_id, "app_id": oauth_app._id, "grant_id": oauth_code.grant_id }).await.unwrap();
+        }
+      }
+    }
+  }
+
+  Ok((
+    StatusCode::OK,
+    [
+      ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+      ( header::ACCESS_CONTROL_ALLOW_METHODS, "POST".into() ),
+      ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+    ],
+    Json(json!({}))
+  ))
+}
\ No newline at end of file
@@ -1,15 +1,16 @@
 use std::sync::Arc;
 
-use argon2::{password_hash::Encoding, Argon2, PasswordHash, PasswordVerifier};
 use axum::{ extract::Query, http::{ header, HeaderMap, StatusCode }, response::IntoResponse, Extension, Json };
 use serde_json::json;
-use bson::{doc, oid::ObjectId};
+use bson::{ doc, oid::ObjectId, Document };
 
-use crate::{ apphandler::AppHandler, structs::apierror::APIError, util::cors::cors };
+use crate::{ apphandler::AppHandler, structs::apierror::APIError, util::{ cors::cors, token } };
 
 #[derive(serde::Deserialize, Debug)]
 pub struct OAuthApplicationToDeleteQuery{
-  pub client_id: String,
+  pub client_id: Option<String>,
+  pub after: Option<String>,
+  pub limit: Option<i64>
 }
 
 pub async fn get(
@@ -17,28 +18,43 @@ pub async fn get(
   Query(query): Query<OAuthApplicationToDeleteQuery>,
   Extension(app): Extension<Arc<AppHandler>>
 ) -> impl IntoResponse{
-  let oauth_app = app.oauth_apps.find_one(doc! { "_id": ObjectId::parse_str(&query.client_id).unwrap() }).await.unwrap();
-  if oauth_app.is_none(){ return Err(APIError::new(500, "Invalid App".into(), &headers)) }
+  let auth = headers.get("Authorization");
+  if auth.is_none() { return Err(APIError::new(401, "Invalid App Key".into(), &headers)) }
 
-  let oauth_app = oauth_app.unwrap();
+  let auth = auth.unwrap().to_str().unwrap().to_owned();
 
-  let auth = headers.get("Authorization").unwrap().to_str().unwrap();
-  if !auth.starts_with("Bearer "){ return Err(APIError::new(401, "Invalid App Key".into(), &headers)) }
+  let app_id = match token::identify_deletion_queue(auth, query.client_id.as_deref(), app.clone()).await{
+    Ok(app_id) => app_id,
+    Err(err) => return Err(APIError::new(401, err.to_string(), &headers))
+  };
 
-  let argon2 = Argon2::default();
-  let auth = auth.split_at(7).1;
+  let mut filter = doc! { "apps_to_delete_data": app_id };
 
-  let valid = argon2.verify_password(auth.as_bytes(), &PasswordHash::parse(&oauth_app.key, Encoding::B64).unwrap()).is_ok();
-  if !valid { return Err(APIError::new(500, "Invalid App Key".into(), &headers)) }
+  // Paged by user ID, pass the last ID you got back as "after" to get the next page
+  if let Some(after) = &query.after {
+    let Ok(after) = ObjectId::parse_str(after) else { return Err(APIError::new(400, "Invalid Cursor".into(), &headers)) };
+    filter.insert("_id", doc! { "$gt": after });
+  }
+
+  let limit = query.limit.unwrap_or(100).clamp(1, 500);
+
+  // Apps only need the ID, the rest of the user document never leaves the server
+  let mut cursor = app.users.clone_with_type::<Document>()
+    .find(filter)
+    .projection(doc! { "_id": 1 })
+    .sort(doc! { "_id": 1 })
+    .limit(limit)
+    .await.unwrap();
 
-  let mut cursor = app.users.find(doc! { "apps_to_delete_data": oauth_app._id }).await.unwrap();
   let mut users = Vec::new();
 
   while cursor.advance().await.unwrap() {
     let user = cursor.deserialize_current().unwrap();
-    users.push(user);
+    users.push(user.get_object_id("_id").unwrap().to_hex());
   }
 
+  let next = if users.len() as i64 == limit { users.last().cloned() } else { None };
+
   Ok((
     StatusCode::OK,
     [
@@ -46,6 +62,6 @@ pub async fn get(
       ( header::ACCESS_CONTROL_ALLOW_METHODS, "GET".into() ),
       ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
     ],
-    Json(json!({ "users": users }))
+    Json(json!({ "users": users, "next": next }))
   ))
 }
\ No newline at end of file
@@ -0,0 +1,69 @@
+use std::sync::Arc;
+
+use axum::{ http::{ header, HeaderMap, StatusCode }, response::IntoResponse, Extension, Json };
+use serde::Deserialize;
+use serde_json::json;
+use bson::{ doc, oid::ObjectId };
+use chrono::Utc;
+
+use crate::{ apphandler::AppHandler, structs::{ apierror::APIError, deletionack::DeletionAcknowledgement }, util::{ cors::cors, token } };
+
+#[derive(Deserialize)]
+pub struct OAuthApplicationToDeleteAckRequest{
+  pub client_id: Option<String>,
+  pub users: Vec<String>
+}
+
+pub async fn post(
+  headers: HeaderMap,
+  Extension(app): Extension<Arc<AppHandler>>,
+  Json(body): Json<OAuthApplicationToDeleteAckRequest>
+) -> impl IntoResponse{
+  let auth = headers.get("Authorization");
+  if auth.is_none() { return Err(APIError::new(401, "Invalid App Key".into(), &headers)) }
+
+  let auth = auth.unwrap().to_str().unwrap().to_owned();
+
+  let app_id = match token::identify_deletion_queue(auth, body.client_id.as_deref(), app.clone()).await{
+    Ok(app_id) => app_id,
+    Err(err) => return Err(APIError::new(401, err.to_string(), &headers))
+  };
+
+  if body.users.len() > 500 { return Err(APIError::new(400, "Too many users, send at most 500 at a time".into(), &headers)) }
+
+  let now = Utc::now().timestamp();
+  let mut acknowledged = Vec::new();
+
+  for user_id in body.users {
+    let Ok(user_id) = ObjectId::parse_str(&user_id) else { continue };
+
+    // Filtering on the queue means only users that were actually waiting on this app get an audit record
+    let res = app.users.update_one(
+      doc! { "_id": user_id, "apps_to_delete_data": app_id },
+      doc! { "$pull": { "apps_to_delete_data": app_id } }
+    ).await.unwrap();
+
+    if res.modified_count == 0 { continue }
+
+    app.deletion_acks.insert_one(DeletionAcknowledgement {
+      _id: ObjectId::new(),
+
+      app_id,
+      user_id,
+
+      acknowledged_on: now
+    }).await.unwrap();
+
+    acknowledged.push(user_id.to_hex());
+  }
+
+  Ok((
+    StatusCode::OK,
+    [
+      ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+      ( header::ACCESS_CONTROL_ALLOW_METHODS, "GET".into() ),
+      ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+    ],
+    Json(json!({ "acknowledged": acknowledged }))
+  ))
+}
\ No newline at end of file
@@ -1,78 +1,349 @@
 use std::sync::Arc;
 
 use argon2::{ password_hash::{ Encoding, SaltString }, Argon2, PasswordHash, PasswordVerifier, PasswordHasher };
-use axum::{ extract::Query, http::{ header, HeaderMap, StatusCode }, response::IntoResponse, Extension, Json };
+use axum::{ extract::Query, http::{ header, HeaderMap, StatusCode }, response::IntoResponse, Extension, Form, Json };
+use anyhow::bail;
 use bson::{ doc, oid::ObjectId };
 use chrono::Utc;
 use rand::{ distributions::Alphanumeric, rngs::OsRng, Rng };
-use serde_json::json;
+use serde_json::{ json, Value };
 
-use crate::{ apphandler::AppHandler, structs::{ apierror::APIError, oauthcode::OAuthCode, oauthsession::OAuthSession }, util::cors::cors };
+use crate::{ apphandler::AppHandler, structs::{ oautherror::OAuthError, oauthapp::OAuthApplication, oauthcode::OAuthCode, oauthsession::OAuthSession, securityevent::SecurityEvent }, util::{ cors::cors, ip::get_ip_from_request, oidc, pkce, redirect_uri, scopes, token } };
 
+pub const DEVICE_CODE_GRANT: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+#[derive(serde::Deserialize, Debug)]
+pub struct OAuthTokenRequest{
+  pub grant_type: String,
+  pub client_id: Option<String>,
+  pub client_secret: Option<String>,
+  pub redirect_uri: Option<String>,
+  pub code: Option<String>,
+  pub refresh_token: Option<String>,
+  pub code_verifier: Option<String>,
+  pub device_code: Option<String>,
+  pub scope: Option<String>
+}
+
+// Only what the legacy endpoint took before, secrets don't belong in a query string where proxies log them
 #[derive(serde::Deserialize, Debug)]
-pub struct OAuthApplicationRequestQuery{
+pub struct OAuthLegacyTokenQuery{
   pub grant_type: String,
-  pub client_id: String,
-  pub redirect_uri: String,
-  pub code: String
+  pub client_id: Option<String>,
+  pub redirect_uri: Option<String>,
+  pub code: Option<String>,
+  pub refresh_token: Option<String>,
+  pub scope: Option<String>,
+
+  // Only here so they can be refused
+  pub client_secret: Option<String>,
+  pub code_verifier: Option<String>
 }
 
+// Legacy endpoint, kept for existing apps that send everything in the query string
 pub async fn get(
   headers: HeaderMap,
-  Query(query): Query<OAuthApplicationRequestQuery>,
+  Query(query): Query<OAuthLegacyTokenQuery>,
   Extension(app): Extension<Arc<AppHandler>>
 ) -> impl IntoResponse{
+  if query.client_secret.is_some() || query.code_verifier.is_some() {
+    return Err(OAuthError::invalid_request("Secrets must be sent in a POST body, not the query string", &headers)) }
+
+  let query = OAuthTokenRequest {
+    grant_type: query.grant_type,
+    client_id: query.client_id,
+    client_secret: None,
+    redirect_uri: query.redirect_uri,
+    code: query.code,
+    refresh_token: query.refresh_token,
+    code_verifier: None,
+    device_code: None,
+    scope: query.scope
+  };
+
+  let res = exchange(&headers, query, app).await;
+  res.map(| res | respond(&headers, res, "GET"))
+}
+
+pub async fn post(
+  headers: HeaderMap,
+  Extension(app): Extension<Arc<AppHandler>>,
+  Form(body): Form<OAuthTokenRequest>
+) -> impl IntoResponse{
+  let res = exchange(&headers, body, app).await;
+  res.map(| res | respond(&headers, res, "POST"))
+}
+
+fn respond( headers: &HeaderMap, res: Value, methods: &str ) -> impl IntoResponse{
+  (
+    StatusCode::OK,
+    [
+      ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(headers) ),
+      ( header::ACCESS_CONTROL_ALLOW_METHODS, methods.into() ),
+      ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() ),
+      ( header::CACHE_CONTROL, "no-store".into() )
+    ],
+    Json(res)
+  )
+}
+
+async fn exchange( headers: &HeaderMap, query: OAuthTokenRequest, app: Arc<AppHandler> ) -> Result<Value, OAuthError>{
   if
     query.grant_type != "authorization_code" &&
-    query.grant_type != "refresh_token" { return Err(APIError::new(400, "Invalid Grant Type.".into(), &headers)); }
+    query.grant_type != "refresh_token" &&
+    query.grant_type != "client_credentials" &&
+    query.grant_type != DEVICE_CODE_GRANT { return Err(OAuthError::new(400, "unsupported_grant_type", "Invalid Grant Type.", headers)); }
 
-  let oauth_app = app.oauth_apps.find_one(doc! { "_id": ObjectId::parse_str(&query.client_id).unwrap() }).await.unwrap();
-  if oauth_app.is_none(){ return Err(APIError::new(500, "Invalid App".into(), &headers)) }
+  let ( client_id, client_secret ) = token::client_auth(headers, query.client_id, query.client_secret)?;
 
-  let oauth_app = oauth_app.unwrap();
-  if !oauth_app.redirect_uris.contains(&query.redirect_uri){ return Err(APIError::new(500, "Invalid Redirect URI".into(), &headers)) }
+  let Ok(client_id) = ObjectId::parse_str(&client_id) else { return Err(OAuthError::invalid_client("Invalid App", headers)) };
 
-  let auth = headers.get("Authorization").unwrap().to_str().unwrap();
-  if !auth.starts_with("Bearer "){ return Err(APIError::new(401, "Invalid App Key".into(), &headers)) }
+  let oauth_app = app.oauth_apps.find_one(doc! { "_id": client_id }).await.unwrap();
+  if oauth_app.is_none(){ return Err(OAuthError::invalid_client("Invalid App", headers)) }
+
+  let oauth_app = oauth_app.unwrap();
+  if oauth_app.suspended { return Err(OAuthError::new(400, "unauthorized_client", "App is suspended", headers)) }
 
   let argon2 = Argon2::default();
   let now = Utc::now().timestamp();
 
-  let auth = auth.split_at(7).1;
+  // Public clients (SPAs, mobile apps) can't hold the app key, they have to prove the code with PKCE instead
+  let is_public = client_secret.is_none();
+
+  if let Some(client_secret) = &client_secret{
+    let valid = argon2.verify_password(client_secret.as_bytes(), &PasswordHash::parse(&oauth_app.key, Encoding::B64).unwrap()).is_ok();
+    if !valid { return Err(OAuthError::invalid_client("Invalid App Key", headers)) }
+  }
+
+  if query.grant_type == "client_credentials" {
+    // App only tokens act as the application itself, so they need the app key
+    if is_public { return Err(OAuthError::invalid_client("Invalid App Key", headers)) }
+
+    let scopes = scopes::parse(&query.scope.unwrap_or_default());
+    for scope in &scopes {
+      if !scopes::is_valid_app(scope){ return Err(OAuthError::new(400, "invalid_scope", "Invalid Scopes", headers)) } }
+
+    let token: String = rand::thread_rng().sample_iter(&Alphanumeric).take(64).map(char::from).collect();
+    let salt = SaltString::generate(&mut OsRng);
+
+    let oauth_session = OAuthSession {
+      _id: ObjectId::new(),
+
+      token: argon2.hash_password(token.as_bytes(), &salt).unwrap().to_string(),
+
+      created_on: now,
+      expires_on: now + 3600,
+
+      app_id: oauth_app._id,
+      app_name: oauth_app.name,
+
+      user_id: None,
+      scopes: scopes.clone(),
+
+      grant_id: None
+    };
+
+    app.oauth_sessions.delete_many(doc! { "expires_on": { "$lt": now }, "user_id": None::<ObjectId>, "app_id": oauth_app._id }).await.unwrap();
+    app.oauth_sessions.insert_one(&oauth_session).await.unwrap();
+
+    return Ok(json!({
+      "access_token": format!("{}{}", oauth_session._id.to_hex(), token),
+      "token_type": "Bearer",
+      "expires_in": 3600,
+      "scope": scopes.join(" ")
+    }))
+  }
+
+  if query.grant_type == DEVICE_CODE_GRANT {
+    if query.device_code.is_none(){ return Err(OAuthError::invalid_request("Missing device_code", headers)) }
+
+    let device_code = query.device_code.unwrap();
+    if device_code.len() < 25 || !device_code.is_char_boundary(24) { return Err(OAuthError::invalid_grant("Invalid Device Code.", headers)) }
+
+    let ( token_id, token ) = device_code.split_at(24);
+    let token_id = ObjectId::parse_str(token_id);
+
+    if token_id.is_err(){ return Err(OAuthError::invalid_grant("Invalid Device Code.", headers)) }
+
+    let oauth_device_code = app.oauth_device_codes.find_one(doc! { "_id": token_id.unwrap(), "app": oauth_app._id }).await.unwrap();
+    if oauth_device_code.is_none(){ return Err(OAuthError::invalid_grant("Invalid Device Code.", headers)) }
+
+    let oauth_device_code = oauth_device_code.unwrap();
+
+    let valid = argon2.verify_password(token.as_bytes(), &PasswordHash::parse(&oauth_device_code.token, Encoding::B64).unwrap()).is_ok();
+    if !valid { return Err(OAuthError::invalid_grant("Invalid Device Code.", headers)) }
+
+    if oauth_device_code.expires_on < now {
+      app.oauth_device_codes.delete_one(doc! { "_id": oauth_device_code._id }).await.unwrap();
+      return Err(OAuthError::new(400, "expired_token", "Device Code has expired.", headers))
+    }
+
+    if oauth_device_code.denied {
+      app.oauth_device_codes.delete_one(doc! { "_id": oauth_device_code._id }).await.unwrap();
+      return Err(OAuthError::new(400, "access_denied", "User denied the request.", headers))
+    }
+
+    if !oauth_device_code.approved || oauth_device_code.user_id.is_none() {
+      // Clients polling faster than the interval get told to back off by another 5 seconds (RFC 8628 3.5)
+      if oauth_device_code.last_polled + oauth_device_code.interval > now {
+        app.oauth_device_codes.update_one(doc! { "_id": oauth_device_code._id }, doc! {
+          "$set": { "last_polled": now },
+          "$inc": { "interval": 5 }
+        }).await.unwrap();
+
+        return Err(OAuthError::new(400, "slow_down", "Polling too fast.", headers))
+      }
+
+      app.oauth_device_codes.update_one(doc! { "_id": oauth_device_code._id }, doc! { "$set": { "last_polled": now } }).await.unwrap();
+      return Err(OAuthError::new(400, "authorization_pending", "Waiting for the user to approve.", headers))
+    }
+
+    // Two polls can both get here after approval, only the one that actually removes the code gets tokens
+    let oauth_device_code = app.oauth_device_codes.find_one_and_delete(doc! { "_id": oauth_device_code._id, "approved": true }).await.unwrap();
+    let Some(oauth_device_code) = oauth_device_code else { return Err(OAuthError::invalid_grant("Invalid Device Code.", headers)) };
+
+    let grant = Grant {
+      id: ObjectId::new(),
+      user_id: oauth_device_code.user_id.unwrap(),
+      scopes: oauth_device_code.scopes,
+      redirect_uri: "".into(),
+      public_client: is_public
+    };
+
+    return issue(app, oauth_app, grant, None).await
+      .map_err(| err | OAuthError::new(500, "server_error", &err.to_string(), headers))
+  }
 
-  let valid = argon2.verify_password(auth.as_bytes(), &PasswordHash::parse(&oauth_app.key, Encoding::B64).unwrap()).is_ok();
-  if !valid { return Err(APIError::new(500, "Invalid App Key".into(), &headers)) }
+  // Standard clients send refresh tokens as refresh_token, older apps put them in code
+  let code = if query.grant_type == "refresh_token" { query.refresh_token.or(query.code) } else{ query.code };
 
-  let ( token_id, token ) = query.code.split_at(24);
+  if code.is_none(){ return Err(OAuthError::invalid_request("Missing code", headers)) }
+  if query.grant_type == "authorization_code" && query.redirect_uri.is_none() { return Err(OAuthError::invalid_request("Missing redirect_uri", headers)) }
 
-  let oauth_code = app.oauth_codes.find_one(doc! { "_id": ObjectId::parse_str(token_id).unwrap() }).await.unwrap();
-  if oauth_code.is_none(){ return Err(APIError::new(500, "Invalid OAuth Code.".into(), &headers)) }
+  let code = code.unwrap();
+
+  if let Some(redirect_uri) = &query.redirect_uri {
+    if !redirect_uri::is_allowed(&oauth_app.redirect_uris, redirect_uri){ return Err(OAuthError::invalid_grant("Invalid Redirect URI", headers)) } }
+
+  if code.len() < 25 || !code.is_char_boundary(24) { return Err(OAuthError::invalid_grant("Invalid OAuth Code.", headers)) }
+
+  let ( token_id, token ) = code.split_at(24);
+  let Ok(token_id) = ObjectId::parse_str(token_id) else { return Err(OAuthError::invalid_grant("Invalid OAuth Code.", headers)) };
+
+  let oauth_code = app.oauth_codes.find_one(doc! { "_id": token_id }).await.unwrap();
+  if oauth_code.is_none(){ return Err(OAuthError::invalid_grant("Invalid OAuth Code.", headers)) }
 
   let oauth_code = oauth_code.unwrap();
   if oauth_code.expires_on < now {
     app.oauth_codes.delete_one(doc! { "_id": oauth_code._id }).await.unwrap();
-    return Err(APIError::new(500, "Invalid OAuth Code.".into(), &headers))
+    return Err(OAuthError::invalid_grant("Invalid OAuth Code.", headers))
   }
 
   if oauth_code.refresh{
-    if query.grant_type != "refresh_token" { return Err(APIError::new(400, "Invalid Grant Type.".into(), &headers)); }
+    if query.grant_type != "refresh_token" { return Err(OAuthError::invalid_grant("Invalid Grant Type.", headers)); }
   } else{
-    if query.grant_type != "authorization_code" { return Err(APIError::new(400, "Invalid Grant Type.".into(), &headers)); }
+    if query.grant_type != "authorization_code" { return Err(OAuthError::invalid_grant("Invalid Grant Type.", headers)); }
   }
 
+  // Refresh tokens don't have to send the redirect URI again (RFC 6749 6), but it has to match if they do
   if
-    query.redirect_uri != oauth_code.redirect_uri ||
-    query.client_id != oauth_code.app.to_hex()
+    ( query.redirect_uri.is_some() && query.redirect_uri.as_ref() != Some(&oauth_code.redirect_uri) ) ||
+    oauth_app._id != oauth_code.app
   {
-    return Err(APIError::new(500, "Invalid OAuth Code.".into(), &headers))
+    return Err(OAuthError::invalid_grant("Invalid OAuth Code.", headers))
   }
 
   let valid = argon2.verify_password(token.as_bytes(), &PasswordHash::parse(&oauth_code.token, Encoding::B64).unwrap()).is_ok();
-  if !valid { return Err(APIError::new(500, "Invalid OAuth Code.".into(), &headers)) }
+  if !valid { return Err(OAuthError::invalid_grant("Invalid OAuth Code.", headers)) }
+
+  if let Some(code_challenge) = &oauth_code.code_challenge{
+    if query.code_verifier.is_none(){ return Err(OAuthError::invalid_grant("Missing Code Verifier.", headers)) }
+
+    let valid = pkce::verify(
+      code_challenge,
+      oauth_code.code_challenge_method.as_deref().unwrap_or("plain"),
+      query.code_verifier.as_deref().unwrap()
+    );
+
+    if !valid { return Err(OAuthError::invalid_grant("Invalid Code Verifier.", headers)) }
+  } else if query.code_verifier.is_some(){
+    return Err(OAuthError::invalid_grant("Invalid Code Verifier.", headers))
+  }
+
+  if is_public && oauth_code.code_challenge.is_none() && !oauth_code.public_client {
+    return Err(OAuthError::invalid_client("Invalid App Key", headers))
+  }
+
+  let grant_id = oauth_code.grant_id.unwrap_or_else(ObjectId::new);
+
+  if oauth_code.refresh {
+    // Only the first request to use a refresh token gets to swap it, so two racing refreshes can't both win
+    let claimed = app.oauth_codes.update_one(
+      doc! { "_id": oauth_code._id, "used": { "$ne": true } },
+      doc! { "$set": { "used": true, "grant_id": grant_id } }
+    ).await.unwrap();
+
+    if claimed.modified_count == 0 {
+      // Someone is replaying an old refresh token, either the client or whoever stole it. We can't tell which, so kill the whole grant
+      app.oauth_codes.delete_many(doc! { "user_id": oauth_code.user_id, "grant_id": grant_id }).await.unwrap();
+      app.oauth_sessions.delete_many(doc! { "user_id": oauth_code.user_id, "grant_id": grant_id }).await.unwrap();
+
+      app.security_events.insert_one(SecurityEvent {
+        _id: ObjectId::new(),
+        event: "refresh_token_reuse".into(),
+
+        user_id: Some(oauth_code.user_id),
+        app_id: Some(oauth_app._id),
+        grant_id: Some(grant_id),
+
+        ip: get_ip_from_request(headers).ok(),
+        created_on: now
+      }).await.unwrap();
+
+      return Err(OAuthError::invalid_grant("Invalid OAuth Code.", headers))
+    }
+
+    // Refreshing rotates the tokens of that one grant, other devices signed in to the same app are left alone
+    app.oauth_sessions.delete_many(doc! { "user_id": oauth_code.user_id, "app_id": oauth_app._id, "grant_id": oauth_code.grant_id }).await.unwrap();
+  } else{
+    app.oauth_codes.delete_one(doc! { "_id": oauth_code._id }).await.unwrap();
+  }
+
+  let grant = Grant {
+    id: grant_id,
+    user_id: oauth_code.user_id,
+    scopes: oauth_code.scopes,
+    redirect_uri: oauth_code.redirect_uri,
+    public_client: is_public
+  };
+
+  issue(app, oauth_app, grant, oauth_code.nonce).await
+    .map_err(| err | OAuthError::new(500, "server_error", &err.to_string(), headers))
+}
+
+// One device or client instance a user has signed in to an app on
+struct Grant{
+  id: ObjectId,
+  user_id: ObjectId,
+  scopes: Vec<String>,
+  redirect_uri: String,
+  public_client: bool
+}
+
+// Creates the access token and refresh token for a grant a user has approved
+async fn issue( app: Arc<AppHandler>, oauth_app: OAuthApplication, grant: Grant, nonce: Option<String> ) -> anyhow::Result<Value>{
+  let Grant { id: grant_id, user_id, scopes, redirect_uri, public_client } = grant;
+
+  let argon2 = Argon2::default();
+  let now = Utc::now().timestamp();
 
   let token: String = rand::thread_rng().sample_iter(&Alphanumeric).take(64).map(char::from).collect();
   let salt = SaltString::generate(&mut OsRng);
 
+  let jwt_access_tokens = oauth_app.jwt_access_tokens;
+
+  // JWT apps still get a session so the grant shows up in account settings and can be revoked,
+  // its own token just never gets handed out
   let oauth_session = OAuthSession {
     _id: ObjectId::new(),
 
@@ -84,13 +355,24 @@ pub async fn get(
     app_id: oauth_app._id,
     app_name: oauth_app.name,
 
-    user_id: oauth_code.user_id,
-    scopes: oauth_code.scopes.clone()
+    user_id: Some(user_id),
+    scopes: scopes.clone(),
+
+    grant_id: Some(grant_id)
   };
 
-  app.oauth_sessions.delete_many(doc! { "user_id": oauth_code.user_id, "app_id": oauth_app._id }).await.unwrap();
+  app.oauth_sessions.delete_many(doc! { "user_id": user_id, "app_id": oauth_app._id, "expires_on": { "$lt": now } }).await.unwrap();
   app.oauth_sessions.insert_one(&oauth_session).await.unwrap();
 
+  let id_token = if scopes.contains(&"openid".to_string()) {
+    let user = app.users.find_one(doc! { "_id": user_id }).await.unwrap();
+    if user.is_none(){ bail!("Invalid OAuth Code.") }
+
+    Some(oidc::id_token(&app, &user.unwrap(), &scopes, oauth_app._id.to_hex(), nonce).await?)
+  } else{
+    None
+  };
+
   let refresh_token: String = rand::thread_rng().sample_iter(&Alphanumeric).take(64).map(char::from).collect();
   let salt = SaltString::generate(&mut OsRng);
 
@@ -99,32 +381,44 @@ pub async fn get(
     token: argon2.hash_password(refresh_token.as_bytes(), &salt).unwrap().to_string(),
 
     app: oauth_app._id,
-    redirect_uri: query.redirect_uri,
+    redirect_uri,
 
     created_on: now,
     expires_on: now + 31557600,
 
     refresh: true,
 
-    user_id: oauth_code.user_id,
-    scopes: oauth_code.scopes
+    user_id,
+    scopes,
+
+    code_challenge: None,
+    code_challenge_method: None,
+
+    nonce: None,
+
+    public_client,
+
+    grant_id: Some(grant_id),
+    used: false
   };
 
-  app.oauth_codes.delete_many(doc! { "user_id": oauth_code.user_id, "app": oauth_app._id }).await.unwrap();
+  app.oauth_codes.delete_many(doc! { "user_id": user_id, "app": oauth_app._id, "expires_on": { "$lt": now } }).await.unwrap();
   app.oauth_codes.insert_one(&ocode).await.unwrap();
 
-  Ok((
-    StatusCode::OK,
-    [
-      ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
-      ( header::ACCESS_CONTROL_ALLOW_METHODS, "GET".into() ),
-      ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
-    ],
-    Json(json!({
-      "access_token": format!("{}{}", oauth_session._id.to_hex(), token),
-      "token_type": "Bearer",
-      "expires_in": 2629800,
-      "refresh_token": format!("{}{}", ocode._id.to_hex(), refresh_token)
-    }))
-  ))
+  let ( access_token, expires_in ) = if jwt_access_tokens {
+    ( oidc::access_token(&app, user_id, &ocode.scopes, oauth_app._id.to_hex(), oauth_session._id).await?, oidc::ACCESS_TOKEN_LIFETIME )
+  } else{
+    ( format!("{}{}", oauth_session._id.to_hex(), token), 2629800 )
+  };
+
+  let mut res = json!({
+    "access_token": access_token,
+    "token_type": "Bearer",
+    "expires_in": expires_in,
+    "refresh_token": format!("{}{}", ocode._id.to_hex(), refresh_token)
+  });
+
+  if let Some(id_token) = id_token { res["id_token"] = id_token.into(); }
+
+  Ok(res)
 }
\ No newline at end of file
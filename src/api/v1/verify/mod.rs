@@ -1,4 +1,6 @@
 pub mod verify_email;
 pub mod verify_mfa;
 pub mod verify_backup;
-pub mod verify;
\ No newline at end of file
+pub mod verify;
+pub mod verify_passkey_options;
+pub mod verify_passkey;
\ No newline at end of file
@@ -0,0 +1,63 @@
+use std::sync::Arc;
+
+use axum::{ http::{ header, HeaderMap, StatusCode }, response::IntoResponse, Extension, Json };
+use serde::Deserialize;
+use serde_json::json;
+use bson::doc;
+
+use crate::{ apphandler::AppHandler, structs::{ apierror::APIError, passkey::PasskeyAssertion }, util::{ cors::cors, ip::get_ip_from_request, token, webauthn } };
+
+#[derive(Deserialize)]
+pub struct VerifyPasskeyRequestBody{
+  token: String,
+
+  #[serde(flatten)]
+  assertion: PasskeyAssertion
+}
+
+pub async fn post(
+  headers: HeaderMap,
+  Extension(app): Extension<Arc<AppHandler>>,
+  Json(body): Json<VerifyPasskeyRequestBody>
+) -> impl IntoResponse{
+  let identity = token::identify(body.token.clone(), app.clone(), get_ip_from_request(&headers).unwrap()).await;
+  if identity.is_err() { return Err(APIError::new(500, identity.unwrap_err().to_string(), &headers)) }
+
+  let ( user, session ) = identity.unwrap();
+  if !user.email_verified { return Err(APIError::new(400, "Email not verified".into(), &headers)) }
+
+  let challenge = webauthn::take_challenge(&app, &body.assertion.challenge_id, "mfa").await;
+  if challenge.is_none(){ return Err(APIError::new(400, "Invalid Challenge".into(), &headers)) }
+
+  // The challenge has to have been made for this login, not some other session
+  let challenge = challenge.unwrap();
+  if challenge.session_id != Some(session._id){ return Err(APIError::new(400, "Invalid Challenge".into(), &headers)) }
+
+  let passkey = webauthn::find_passkey(&user, &body.assertion.credential_id);
+  if passkey.is_none(){ return Err(APIError::new(400, "Invalid Passkey".into(), &headers)) }
+
+  let passkey = passkey.unwrap();
+
+  let sign_count = webauthn::verify_assertion(&challenge, passkey, &body.assertion, false);
+  if sign_count.is_err(){ return Err(APIError::new(400, "Invalid Passkey".into(), &headers)) }
+
+  webauthn::update_sign_count(&app, user._id, &passkey.id, sign_count.unwrap()).await;
+
+  if !session.valid{
+    app.sessions.update_one(
+      doc! { "_id": session._id }, 
+      doc! { "$set": { "valid": true } }
+    ).await.unwrap();
+  }
+
+  Ok((
+    StatusCode::OK,
+    [
+      ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+      ( header::ACCESS_CONTROL_ALLOW_METHODS, "POST".into() ),
+      ( header::SET_COOKIE, format!("token={}; Max-Age=604800; Domain=idapi-jye3bcyp.phazed.xyz; Path=/api; HttpOnly; Secure; SameSite=Strict", body.token) ),
+      ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+    ],
+    Json(json!({ "PROCEDURE": "NEXT" }))
+  ))
+}
\ No newline at end of file
@@ -0,0 +1,37 @@
+use std::sync::Arc;
+
+use axum::{ http::{ header, HeaderMap, StatusCode }, response::IntoResponse, Extension, Json };
+use serde::Deserialize;
+
+use crate::{ apphandler::AppHandler, structs::apierror::APIError, util::{ cors::cors, ip::get_ip_from_request, token, webauthn } };
+
+#[derive(Deserialize)]
+pub struct VerifyPasskeyOptionsRequestBody{
+  token: String
+}
+
+pub async fn post(
+  headers: HeaderMap,
+  Extension(app): Extension<Arc<AppHandler>>,
+  Json(body): Json<VerifyPasskeyOptionsRequestBody>
+) -> impl IntoResponse{
+  let identity = token::identify(body.token.clone(), app.clone(), get_ip_from_request(&headers).unwrap()).await;
+  if identity.is_err() { return Err(APIError::new(500, identity.unwrap_err().to_string(), &headers)) }
+
+  let ( user, session ) = identity.unwrap();
+  if !user.email_verified { return Err(APIError::new(400, "Email not verified".into(), &headers)) }
+  if user.passkeys.is_empty() { return Err(APIError::new(400, "No passkeys".into(), &headers)) }
+
+  let Some(rp_id) = webauthn::rp_id(&headers) else { return Err(APIError::new(400, "Invalid Origin".into(), &headers)) };
+  let challenge = webauthn::new_challenge(&app, "mfa", rp_id, Some(user._id), Some(session._id)).await;
+
+  Ok((
+    StatusCode::OK,
+    [
+      ( header::ACCESS_CONTROL_ALLOW_ORIGIN, cors(&headers) ),
+      ( header::ACCESS_CONTROL_ALLOW_METHODS, "POST".into() ),
+      ( header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".into() )
+    ],
+    Json(webauthn::request_options(&challenge, &user.passkeys, "preferred"))
+  ))
+}
\ No newline at end of file
@@ -1,17 +1,34 @@
 use std::{env, sync::Arc};
 
 use mongodb::{options::ClientOptions, Client, Collection};
+use tokio::sync::RwLock;
 use s3::{ creds::Credentials, Bucket, Region };
 
-use crate::structs::{oauthapp::OAuthApplication, oauthcode::OAuthCode, oauthsession::OAuthSession, session::Session, user::User};
+use crate::{ structs::{deletionack::DeletionAcknowledgement, magiclink::MagicLink, oauthapp::OAuthApplication, oauthcode::OAuthCode, oauthconsent::OAuthConsent, oauthdevicecode::OAuthDeviceCode, oauthsession::OAuthSession, passkey::WebAuthnChallenge, ratelimit::RateLimitHit, securityevent::SecurityEvent, session::Session, signingkey::SigningKeyPair, upstream::{ UpstreamProvider, UpstreamState }, user::User, webhookdelivery::WebhookDelivery}, util::{ keys::LoadedKey, upstream } };
 
 #[derive(Debug)]
 pub struct AppHandler{
   pub users: Collection<User>,
   pub sessions: Collection<Session>,
+  pub webauthn_challenges: Collection<WebAuthnChallenge>,
+  pub magic_links: Collection<MagicLink>,
+  pub upstream_states: Collection<UpstreamState>,
+  pub rate_limits: Collection<RateLimitHit>,
   pub oauth_apps: Collection<OAuthApplication>,
   pub oauth_sessions: Collection<OAuthSession>,
   pub oauth_codes: Collection<OAuthCode>,
+  pub oauth_device_codes: Collection<OAuthDeviceCode>,
+  pub oauth_consents: Collection<OAuthConsent>,
+  pub security_events: Collection<SecurityEvent>,
+  pub webhook_deliveries: Collection<WebhookDelivery>,
+  pub deletion_acks: Collection<DeletionAcknowledgement>,
+  pub signing_keys: Collection<SigningKeyPair>,
+
+  // Login providers from the config file, see util::upstream
+  pub upstream_providers: Vec<UpstreamProvider>,
+
+  // Decrypted signing keys and when they were loaded, see util::keys
+  pub key_cache: RwLock<Option<( i64, Arc<Vec<LoadedKey>> )>>,
 
   r2: R2
 }
@@ -26,10 +43,25 @@ impl AppHandler{
     Ok(Arc::new(Self {
       users: db.collection("Users"),
       sessions: db.collection("Sessions"),
+      webauthn_challenges: db.collection("WebAuthnChallenges"),
+      magic_links: db.collection("MagicLinks"),
+      upstream_states: db.collection("UpstreamStates"),
+      rate_limits: db.collection("RateLimits"),
 
       oauth_apps: db.collection("OAuthApplications"),
       oauth_sessions: db.collection("OAuthSessions"),
       oauth_codes: db.collection("OAuthCodes"),
+      oauth_device_codes: db.collection("OAuthDeviceCodes"),
+      oauth_consents: db.collection("OAuthConsents"),
+
+      security_events: db.collection("SecurityEvents"),
+      webhook_deliveries: db.collection("WebhookDeliveries"),
+      deletion_acks: db.collection("DeletionAcknowledgements"),
+
+      upstream_providers: upstream::load().await?,
+
+      signing_keys: db.collection("SigningKeys"),
+      key_cache: RwLock::new(None),
 
       r2: R2::new().unwrap()
     }))
@@ -17,8 +17,14 @@ async fn handler_404() -> impl IntoResponse {
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
   dotenvy::dotenv()?;
+  env_logger::init();
 
   let handler = AppHandler::new().await?;
+  util::keys::ensure(&handler).await?;
+  util::ratelimit::create_indexes(&handler).await?;
+
+  tokio::spawn(util::webhook::worker(handler.clone()));
+  tokio::spawn(util::keys::worker(handler.clone()));
 
   let app = Router::new()
     .route("/api/v1/status", options(util::cors::options))
@@ -27,9 +33,57 @@ async fn main() -> anyhow::Result<()> {
     .route("/api/v1/dev/add_app", options(util::cors::options))
     .route("/api/v1/dev/add_app", put(api::v1::dev::add_app::put))
 
+    .route("/api/v1/dev/apps", options(util::cors::options))
+    .route("/api/v1/dev/apps", get(api::v1::dev::apps::get))
+
+    .route("/api/v1/dev/app", options(util::cors::options))
+    .route("/api/v1/dev/app", get(api::v1::dev::app::get))
+
+    .route("/api/v1/dev/update_app", options(util::cors::options))
+    .route("/api/v1/dev/update_app", put(api::v1::dev::update_app::put))
+
+    .route("/api/v1/dev/rotate_key", options(util::cors::options))
+    .route("/api/v1/dev/rotate_key", put(api::v1::dev::rotate_key::put))
+
+    .route("/api/v1/dev/delete_app", options(util::cors::options))
+    .route("/api/v1/dev/delete_app", delete(api::v1::dev::delete_app::del))
+
+    .route("/api/v1/dev/webhook", options(util::cors::options))
+    .route("/api/v1/dev/webhook", put(api::v1::dev::webhook::put))
+
+    .route("/api/v1/dev/logo", options(util::cors::options))
+    .route("/api/v1/dev/logo", put(api::v1::dev::logo::put))
+
+    .route("/api/v1/admin/rotate_keys", options(util::cors::options))
+    .route("/api/v1/admin/rotate_keys", put(api::v1::admin::rotate_keys::put))
+
+    .route("/api/v1/admin/update_app", options(util::cors::options))
+    .route("/api/v1/admin/update_app", put(api::v1::admin::update_app::put))
+
     .route("/api/v1/auth/tunnel", options(util::cors::options))
     .route("/api/v1/auth/tunnel", get(api::v1::auth::tunnel::get))
 
+    .route("/api/v1/auth/passkey/options", options(util::cors::options))
+    .route("/api/v1/auth/passkey/options", get(api::v1::auth::passkey_options::get))
+
+    .route("/api/v1/auth/passkey", options(util::cors::options))
+    .route("/api/v1/auth/passkey", post(api::v1::auth::passkey::post))
+
+    .route("/api/v1/auth/magic_link", options(util::cors::options))
+    .route("/api/v1/auth/magic_link", post(api::v1::auth::magic_link::post))
+
+    .route("/api/v1/auth/magic_link/redeem", options(util::cors::options))
+    .route("/api/v1/auth/magic_link/redeem", post(api::v1::auth::magic_link_redeem::post))
+
+    .route("/api/v1/auth/upstream/providers", options(util::cors::options))
+    .route("/api/v1/auth/upstream/providers", get(api::v1::auth::upstream_providers::get))
+
+    .route("/api/v1/auth/upstream/login", options(util::cors::options))
+    .route("/api/v1/auth/upstream/login", get(api::v1::auth::upstream_login::get))
+
+    .route("/api/v1/auth/upstream/callback", options(util::cors::options))
+    .route("/api/v1/auth/upstream/callback", post(api::v1::auth::upstream_callback::post))
+
     .route("/api/v1/verification", options(util::cors::options))
     .route("/api/v1/verification", get(api::v1::verification::get))
 
@@ -45,6 +99,12 @@ async fn main() -> anyhow::Result<()> {
     .route("/api/v1/verification/verify", options(util::cors::options))
     .route("/api/v1/verification/verify", post(api::v1::verify::verify::post))
 
+    .route("/api/v1/verification/verify_passkey/options", options(util::cors::options))
+    .route("/api/v1/verification/verify_passkey/options", post(api::v1::verify::verify_passkey_options::post))
+
+    .route("/api/v1/verification/verify_passkey", options(util::cors::options))
+    .route("/api/v1/verification/verify_passkey", post(api::v1::verify::verify_passkey::post))
+
     .route("/api/v1/profile", options(util::cors::options))
     .route("/api/v1/profile", get(api::v1::profile::get))
 
@@ -93,6 +153,36 @@ async fn main() -> anyhow::Result<()> {
     .route("/api/v1/account/remove_oauth_app", options(util::cors::options))
     .route("/api/v1/account/remove_oauth_app", get(api::v1::account::remove_oauth_app::get))
 
+    .route("/api/v1/account/passkeys", options(util::cors::options))
+    .route("/api/v1/account/passkeys", get(api::v1::account::passkeys::get))
+
+    .route("/api/v1/account/passkey_options", options(util::cors::options))
+    .route("/api/v1/account/passkey_options", get(api::v1::account::passkey_options::get))
+
+    .route("/api/v1/account/add_passkey", options(util::cors::options))
+    .route("/api/v1/account/add_passkey", put(api::v1::account::add_passkey::put))
+
+    .route("/api/v1/account/remove_passkey", options(util::cors::options))
+    .route("/api/v1/account/remove_passkey", delete(api::v1::account::remove_passkey::delete))
+
+    .route("/api/v1/account/linked_identities", options(util::cors::options))
+    .route("/api/v1/account/linked_identities", get(api::v1::account::linked_identities::get))
+
+    .route("/api/v1/account/link_provider", options(util::cors::options))
+    .route("/api/v1/account/link_provider", get(api::v1::account::link_provider::get))
+
+    .route("/api/v1/account/unlink_provider", options(util::cors::options))
+    .route("/api/v1/account/unlink_provider", delete(api::v1::account::unlink_provider::delete))
+
+    .route("/api/v1/account/reauthenticate_options", options(util::cors::options))
+    .route("/api/v1/account/reauthenticate_options", get(api::v1::account::reauthenticate_options::get))
+
+    .route("/api/v1/account/reauthenticate", options(util::cors::options))
+    .route("/api/v1/account/reauthenticate", put(api::v1::account::reauthenticate::put))
+
+    .route("/api/v1/account/reauthenticate_upstream", options(util::cors::options))
+    .route("/api/v1/account/reauthenticate_upstream", get(api::v1::account::reauthenticate_upstream::get))
+
     .route("/api/v1/oauth/app", options(util::cors::options))
     .route("/api/v1/oauth/app", get(api::v1::oauth::app::get))
 
@@ -101,6 +191,7 @@ async fn main() -> anyhow::Result<()> {
 
     .route("/api/v1/oauth/token", options(util::cors::options))
     .route("/api/v1/oauth/token", get(api::v1::oauth::token::get))
+    .route("/api/v1/oauth/token", post(api::v1::oauth::token::post))
 
     .route("/api/v1/oauth/profile", options(util::cors::options))
     .route("/api/v1/oauth/profile", get(api::v1::oauth::profile::get))
@@ -108,6 +199,30 @@ async fn main() -> anyhow::Result<()> {
     .route("/api/v1/oauth/to_delete", options(util::cors::options))
     .route("/api/v1/oauth/to_delete", get(api::v1::oauth::to_delete::get))
 
+    .route("/api/v1/oauth/to_delete/ack", options(util::cors::options))
+    .route("/api/v1/oauth/to_delete/ack", post(api::v1::oauth::to_delete_ack::post))
+
+    .route("/api/v1/oauth/introspect", options(util::cors::options))
+    .route("/api/v1/oauth/introspect", post(api::v1::oauth::introspect::post))
+
+    .route("/api/v1/oauth/revoke", options(util::cors::options))
+    .route("/api/v1/oauth/revoke", post(api::v1::oauth::revoke::post))
+
+    .route("/api/v1/oauth/device/code", options(util::cors::options))
+    .route("/api/v1/oauth/device/code", post(api::v1::oauth::device_code::post))
+
+    .route("/api/v1/oauth/device", options(util::cors::options))
+    .route("/api/v1/oauth/device", get(api::v1::oauth::device::get))
+
+    .route("/api/v1/oauth/device/authorize", options(util::cors::options))
+    .route("/api/v1/oauth/device/authorize", put(api::v1::oauth::device_authorize::put))
+
+    .route("/api/v1/oauth/jwks", options(util::cors::options))
+    .route("/api/v1/oauth/jwks", get(api::v1::oauth::jwks::get))
+
+    .route("/.well-known/openid-configuration", options(util::cors::options))
+    .route("/.well-known/openid-configuration", get(api::v1::oauth::openid_configuration::get))
+
     .route("/api/v1/patreon/link", options(util::cors::options))
     .route("/api/v1/patreon/link", get(api::v1::patreon::link::get))
 
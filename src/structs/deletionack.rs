@@ -0,0 +1,13 @@
+use bson::oid::ObjectId;
+use serde::{ Deserialize, Serialize };
+
+// Audit record for an app confirming it purged a user's data
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DeletionAcknowledgement{
+  pub _id: ObjectId,
+
+  pub app_id: ObjectId,
+  pub user_id: ObjectId,
+
+  pub acknowledged_on: i64
+}
\ No newline at end of file
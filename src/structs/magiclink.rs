@@ -0,0 +1,14 @@
+use bson::oid::ObjectId;
+use serde::{ Deserialize, Serialize };
+
+// Outstanding login link, see util::magic_link
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MagicLink{
+  pub _id: ObjectId,
+  pub user_id: ObjectId,
+
+  pub ip: Option<String>,
+
+  pub created_on: i64,
+  pub expires_on: i64
+}
\ No newline at end of file
@@ -2,9 +2,21 @@ pub mod user;
 pub mod tunnel;
 pub mod ipinfo;
 pub mod session;
+pub mod passkey;
+pub mod magiclink;
+pub mod upstream;
+pub mod ratelimit;
 pub mod apierror;
+pub mod oautherror;
 pub mod patreon;
 
 pub mod oauthapp;
 pub mod oauthcode;
 pub mod oauthsession;
+pub mod oauthdevicecode;
+pub mod oauthconsent;
+
+pub mod securityevent;
+pub mod webhookdelivery;
+pub mod deletionack;
+pub mod signingkey;
@@ -11,5 +11,83 @@ pub struct OAuthApplication{
   pub key: String,
   pub redirect_uris: Vec<String>,
 
-  pub owner_id: ObjectId
+  pub owner_id: ObjectId,
+
+  // Where user lifecycle events get sent, see util::webhook
+  #[serde(default)]
+  pub webhook_url: Option<String>,
+  #[serde(default)]
+  pub webhook_secret: Option<String>,
+
+  // Issue short lived JWT access tokens that resource servers can check offline
+  #[serde(default)]
+  pub jwt_access_tokens: bool,
+
+  // Branding shown on the consent screen, logo is an id under /id/app_logos/{_id} in R2
+  #[serde(default)]
+  pub description: Option<String>,
+  #[serde(default)]
+  pub homepage_url: Option<String>,
+  #[serde(default)]
+  pub privacy_policy_url: Option<String>,
+  #[serde(default)]
+  pub terms_url: Option<String>,
+  #[serde(default)]
+  pub logo: Option<String>,
+
+  // Set by admins, see api::v1::admin::update_app
+  #[serde(default)]
+  pub verified: bool,
+  #[serde(default)]
+  pub suspended: bool,
+  #[serde(default)]
+  pub suspended_reason: Option<String>
+}
+
+// Same as OAuthApplication but without the key, safe to send to the developer portal
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PublicOAuthApplication{
+  pub _id: String,
+
+  pub name: String,
+  pub allow_skip: bool,
+
+  pub redirect_uris: Vec<String>,
+
+  pub owner_id: String,
+
+  pub webhook_url: Option<String>,
+  pub jwt_access_tokens: bool,
+
+  pub description: Option<String>,
+  pub homepage_url: Option<String>,
+  pub privacy_policy_url: Option<String>,
+  pub terms_url: Option<String>,
+  pub logo: Option<String>,
+
+  pub verified: bool,
+  pub suspended: bool,
+  pub suspended_reason: Option<String>
+}
+
+impl PublicOAuthApplication{
+  pub fn from_app( app: OAuthApplication ) -> Self{
+    PublicOAuthApplication {
+      _id: app._id.to_hex(),
+      name: app.name,
+      allow_skip: app.allow_skip,
+      redirect_uris: app.redirect_uris,
+      owner_id: app.owner_id.to_hex(),
+      webhook_url: app.webhook_url,
+      jwt_access_tokens: app.jwt_access_tokens,
+      description: app.description,
+      homepage_url: app.homepage_url,
+      privacy_policy_url: app.privacy_policy_url,
+      terms_url: app.terms_url,
+      logo: app.logo,
+      verified: app.verified,
+      suspended: app.suspended,
+      suspended_reason: app.suspended_reason
+    }
+  }
 }
\ No newline at end of file
@@ -15,5 +15,24 @@ pub struct OAuthCode{
   pub refresh: bool,
 
   pub user_id: ObjectId,
-  pub scopes: Vec<String>
+  pub scopes: Vec<String>,
+
+  // PKCE (RFC 7636), only set on authorization codes
+  pub code_challenge: Option<String>,
+  pub code_challenge_method: Option<String>,
+
+  // OpenID Connect nonce, echoed back in the ID token
+  pub nonce: Option<String>,
+
+  // Refresh tokens issued to clients that authenticated with PKCE instead of an app key
+  #[serde(default)]
+  pub public_client: bool,
+
+  // Only set on refresh tokens, see OAuthSession. Every refresh token issued for a grant shares it
+  #[serde(default)]
+  pub grant_id: Option<ObjectId>,
+
+  // Used refresh tokens are kept until they expire so replaying one can be caught
+  #[serde(default)]
+  pub used: bool
 }
\ No newline at end of file
@@ -0,0 +1,16 @@
+use bson::oid::ObjectId;
+use serde::{ Deserialize, Serialize };
+
+// What a user has agreed to share with an app, one per user and app
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OAuthConsent{
+  pub _id: ObjectId,
+
+  pub user_id: ObjectId,
+  pub app_id: ObjectId,
+
+  pub scopes: Vec<String>,
+
+  pub created_on: i64,
+  pub updated_on: i64
+}
\ No newline at end of file
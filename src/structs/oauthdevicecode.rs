@@ -0,0 +1,25 @@
+use bson::oid::ObjectId;
+use serde::{ Deserialize, Serialize };
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OAuthDeviceCode{
+  pub _id: ObjectId,
+  pub token: String,
+
+  // Short code the user types in on another device, stored without the dash
+  pub user_code: String,
+
+  pub app: ObjectId,
+  pub scopes: Vec<String>,
+
+  pub created_on: i64,
+  pub expires_on: i64,
+
+  pub interval: i64,
+  pub last_polled: i64,
+
+  pub approved: bool,
+  pub denied: bool,
+
+  pub user_id: Option<ObjectId>
+}
\ No newline at end of file
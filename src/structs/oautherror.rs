@@ -0,0 +1,65 @@
+use axum::{ body::Body, http::HeaderMap, response::{ IntoResponse, Response } };
+use serde_json::json;
+
+use crate::util::cors::ALLOWED_ORIGINS;
+
+// RFC 6749 5.2 error response, used by the token endpoint instead of APIError
+pub struct OAuthError{
+  code: u16,
+  error: String,
+  description: String,
+  origin: String
+}
+
+impl OAuthError{
+  pub fn new( code: u16, error: &str, description: &str, headers: &HeaderMap ) -> Self{
+    let origin = headers.get("Origin");
+
+    Self {
+      code,
+      error: error.to_owned(),
+      description: description.to_owned(),
+      origin: if let Some(origin) = origin {
+        origin.to_str().unwrap().to_owned()
+      } else{
+        "https://phaz.uk".into()
+      }
+    }
+  }
+
+  pub fn invalid_request( description: &str, headers: &HeaderMap ) -> Self{
+    Self::new(400, "invalid_request", description, headers)
+  }
+
+  pub fn invalid_client( description: &str, headers: &HeaderMap ) -> Self{
+    Self::new(401, "invalid_client", description, headers)
+  }
+
+  pub fn invalid_grant( description: &str, headers: &HeaderMap ) -> Self{
+    Self::new(400, "invalid_grant", description, headers)
+  }
+}
+
+impl IntoResponse for OAuthError{
+  fn into_response(self) -> Response {
+    let origin = if ALLOWED_ORIGINS.contains(&self.origin.as_str()){
+      self.origin.to_owned()
+    } else{
+      "".into()
+    };
+
+    let mut res = Response::builder()
+      .status(self.code)
+      .header("access-control-allow-credentials", "true")
+      .header("access-control-allow-origin", origin)
+      .header("access-control-allow-methods", "GET,POST,PUT,DELETE,OPTIONS")
+      .header("content-type", "application/json")
+      .header("cache-control", "no-store");
+
+    if self.error == "invalid_client" { res = res.header("www-authenticate", "Basic realm=\"phazeid\""); }
+
+    res
+      .body(Body::from(json!({ "error": self.error, "error_description": self.description }).to_string()))
+      .unwrap()
+  }
+}
\ No newline at end of file
@@ -13,6 +13,11 @@ pub struct OAuthSession{
   pub app_id: ObjectId,
   pub app_name: String,
 
-  pub user_id: ObjectId,
-  pub scopes: Vec<String>
+  // None for app only tokens from the client_credentials grant
+  pub user_id: Option<ObjectId>,
+  pub scopes: Vec<String>,
+
+  // Groups the access and refresh tokens of one device, None for app only tokens and older sessions
+  #[serde(default)]
+  pub grant_id: Option<ObjectId>
 }
\ No newline at end of file
@@ -0,0 +1,40 @@
+use bson::oid::ObjectId;
+use serde::{ Deserialize, Serialize };
+
+// A WebAuthn credential, stored on the user it belongs to
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Passkey{
+  pub id: String, // base64url credential ID
+  pub name: String,
+
+  pub public_key: String, // base64url SEC1 encoded P-256 point
+  pub sign_count: u32,
+
+  pub created_on: i64,
+  pub last_used: i64
+}
+
+// Outstanding registration or assertion ceremony, see util::webauthn
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WebAuthnChallenge{
+  pub _id: ObjectId,
+  pub kind: String, // "register", "login" or "mfa"
+  pub challenge: String,
+  pub rp_id: String,
+
+  pub user_id: Option<ObjectId>,
+  pub session_id: Option<ObjectId>,
+
+  pub expires_on: i64
+}
+
+// What navigator.credentials.get() hands back, everything base64url encoded
+#[derive(Debug, Deserialize)]
+pub struct PasskeyAssertion{
+  pub challenge_id: String,
+  pub credential_id: String,
+  pub client_data_json: String,
+  pub authenticator_data: String,
+  pub signature: String,
+  pub user_handle: Option<String>
+}
\ No newline at end of file
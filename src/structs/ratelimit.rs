@@ -0,0 +1,12 @@
+use bson::{ oid::ObjectId, DateTime };
+use serde::{ Deserialize, Serialize };
+
+// One counted attempt against a limit, see util::ratelimit
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RateLimitHit{
+  pub _id: ObjectId,
+  pub key: String,
+
+  pub created_on: i64,
+  pub expires_at: DateTime // Mongo's TTL indexes only work on dates
+}
\ No newline at end of file
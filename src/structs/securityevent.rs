@@ -0,0 +1,15 @@
+use bson::oid::ObjectId;
+use serde::{ Deserialize, Serialize };
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SecurityEvent{
+  pub _id: ObjectId,
+  pub event: String,
+
+  pub user_id: Option<ObjectId>,
+  pub app_id: Option<ObjectId>,
+  pub grant_id: Option<ObjectId>,
+
+  pub ip: Option<String>,
+  pub created_on: i64
+}
\ No newline at end of file
@@ -14,7 +14,11 @@ pub struct Session{
   pub loc: IPInfo,
   pub valid: bool,
   pub challenge_code: Option<String>,
-  pub user_id: ObjectId
+  pub user_id: ObjectId,
+
+  // Sudo mode, set by re-proving a factor through /account/reauthenticate
+  #[serde(default)]
+  pub elevated_until: i64
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -24,6 +28,7 @@ pub struct PublicSession{
   pub expires_on: i64,
   pub loc: Option<IPInfo>,
   pub app_name: Option<String>,
+  pub scopes: Option<Vec<String>>,
   pub is_this: bool
 }
 
@@ -35,6 +40,7 @@ impl PublicSession{
       expires_on: session.expires_on,
       loc: Some(session.loc),
       app_name: None,
+      scopes: None,
       is_this
     }
   }
@@ -46,6 +52,7 @@ impl PublicSession{
       expires_on: session.expires_on,
       loc: None,
       app_name: Some(session.app_name),
+      scopes: Some(session.scopes),
       is_this
     }
   }
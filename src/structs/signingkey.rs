@@ -0,0 +1,18 @@
+use bson::oid::ObjectId;
+use serde::{ Deserialize, Serialize };
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SigningKeyPair{
+  pub _id: ObjectId,
+  pub kid: String,
+
+  // PKCS8 PEM, encrypted with a key derived from ROOT_KEY (see util::encrypt)
+  pub private_key: String,
+
+  pub created_on: i64,
+  pub active: bool,
+
+  // Set when the key is rotated out, it's still published and checked until then
+  pub retired_on: Option<i64>,
+  pub verify_until: Option<i64>
+}
\ No newline at end of file
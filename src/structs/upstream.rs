@@ -0,0 +1,67 @@
+use bson::oid::ObjectId;
+use serde::{ Deserialize, Serialize };
+
+fn default_subject_field() -> String { "sub".into() }
+fn default_username_field() -> String { "preferred_username".into() }
+fn default_email_field() -> String { "email".into() }
+
+// One entry in the upstream providers config file, see util::upstream
+#[derive(Debug, Deserialize, Clone)]
+pub struct UpstreamProvider{
+  pub id: String,
+  pub name: String,
+
+  pub client_id: String,
+  pub client_secret_env: String, // Name of the env var holding the secret, so it stays out of the config file
+  pub redirect_uri: String,
+  pub scope: String,
+
+  // OIDC providers can just give an issuer, the endpoints get filled in from discovery
+  pub issuer: Option<String>,
+  pub authorization_endpoint: Option<String>,
+  pub token_endpoint: Option<String>,
+  pub userinfo_endpoint: Option<String>,
+
+  #[serde(default)]
+  pub pkce: bool,
+
+  // Where to find things in the userinfo response
+  #[serde(default = "default_subject_field")]
+  pub subject_field: String,
+  #[serde(default = "default_username_field")]
+  pub username_field: String,
+  #[serde(default = "default_email_field")]
+  pub email_field: String,
+  pub email_verified_field: Option<String>,
+
+  // For providers that only ever hand out verified emails but don't say so
+  #[serde(default)]
+  pub trust_email: bool
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LinkedIdentity{
+  pub provider: String,
+  pub subject: String,
+
+  pub username: Option<String>,
+  pub email: Option<String>,
+
+  pub linked_on: i64
+}
+
+// Outstanding trip to an upstream provider, looked up by state when it comes back
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UpstreamState{
+  pub _id: ObjectId,
+  pub state: String,
+  pub provider: String,
+  pub mode: String, // "login", "link" or "reauthenticate"
+
+  pub user_id: Option<ObjectId>,
+  #[serde(default)]
+  pub session_id: Option<ObjectId>,
+  pub code_verifier: Option<String>,
+
+  pub expires_on: i64
+}
\ No newline at end of file
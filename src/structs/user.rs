@@ -1,6 +1,8 @@
 use bson::oid::ObjectId;
 use serde::{ Deserialize, Serialize };
 
+use super::{ passkey::Passkey, upstream::LinkedIdentity };
+
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct UserEmailUpdate{
   pub email: String,
@@ -37,6 +39,13 @@ pub struct User{
   pub mfa_string: Option<String>,
   pub backup_codes: Vec<String>,
 
+  #[serde(default)]
+  pub passkeys: Vec<Passkey>,
+
+  // Accounts on upstream providers that can be used to log in, see util::upstream
+  #[serde(default)]
+  pub linked_identities: Vec<LinkedIdentity>,
+
   pub roles: Vec<String>,
   pub allowed_apps: Vec<ObjectId>,
 
@@ -0,0 +1,19 @@
+use bson::oid::ObjectId;
+use serde::{ Deserialize, Serialize };
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WebhookDelivery{
+  pub _id: ObjectId,
+
+  pub app_id: ObjectId,
+  pub event: String,
+  pub payload: String, // JSON body, kept as a string so the signature always matches what we retry with
+
+  pub created_on: i64,
+  pub next_attempt: i64,
+  pub attempts: u32,
+
+  pub delivered: bool,
+  pub failed: bool,
+  pub last_error: Option<String>
+}
\ No newline at end of file
@@ -7,8 +7,9 @@ use rand::{distributions::Alphanumeric, Rng};
 
 use crate::apphandler::R2;
 
-pub async fn upload<'a>( user_id: ObjectId, current_avatar: String, image: Field<'a>, r2: &R2 ) -> anyhow::Result<String>{
-  let buff = image.bytes().await?;
+// Shared by user avatars and app logos, both have to be a 300x300 PNG
+pub fn check_png( buff: &[u8] ) -> anyhow::Result<()>{
+  if buff.len() < 24 { bail!("Image is not a PNG file") }
 
   if buff[0] != 0x89
     || buff[1] != 0x50
@@ -30,10 +31,30 @@ pub async fn upload<'a>( user_id: ObjectId, current_avatar: String, image: Field
     width != 300 ||
     height != 300 { bail!("Image is not correct dimensions") }
 
+  Ok(())
+}
+
+pub async fn upload<'a>( user_id: ObjectId, current_avatar: String, image: Field<'a>, r2: &R2 ) -> anyhow::Result<String>{
+  let buff = image.bytes().await?;
+  check_png(&buff)?;
+
   r2.delete_file(format!("/id/avatars/{}/{}.png", user_id, current_avatar)).await?;
 
   let avatar_id: String = rand::thread_rng().sample_iter(&Alphanumeric).take(16).map(char::from).collect();
   r2.upload_file(format!("/id/avatars/{}/{}.png", user_id, avatar_id), &buff, "image/png").await.unwrap();
 
   Ok(avatar_id)
+}
+
+pub async fn upload_logo<'a>( app_id: ObjectId, current_logo: Option<String>, image: Field<'a>, r2: &R2 ) -> anyhow::Result<String>{
+  let buff = image.bytes().await?;
+  check_png(&buff)?;
+
+  if let Some(current_logo) = current_logo {
+    r2.delete_file(format!("/id/app_logos/{}/{}.png", app_id, current_logo)).await?; }
+
+  let logo_id: String = rand::thread_rng().sample_iter(&Alphanumeric).take(16).map(char::from).collect();
+  r2.upload_file(format!("/id/app_logos/{}/{}.png", app_id, logo_id), &buff, "image/png").await?;
+
+  Ok(logo_id)
 }
\ No newline at end of file
@@ -0,0 +1,34 @@
+use bson::{ doc, oid::ObjectId };
+use chrono::Utc;
+use mongodb::options::UpdateOptions;
+
+use crate::apphandler::AppHandler;
+
+pub async fn granted_scopes( app: &AppHandler, user_id: ObjectId, app_id: ObjectId ) -> Vec<String>{
+  let consent = app.oauth_consents.find_one(doc! { "user_id": user_id, "app_id": app_id }).await.unwrap();
+  consent.map(| x | x.scopes).unwrap_or_default()
+}
+
+// True when the user already agreed to every one of these scopes, so the consent screen can be skipped
+pub async fn covers( app: &AppHandler, user_id: ObjectId, app_id: ObjectId, scopes: &[String] ) -> bool{
+  let granted = granted_scopes(app, user_id, app_id).await;
+  scopes.iter().all(| x | granted.contains(x))
+}
+
+// Adds to what's been granted, scopes from earlier approvals are kept
+pub async fn grant( app: &AppHandler, user_id: ObjectId, app_id: ObjectId, scopes: &[String] ){
+  let now = Utc::now().timestamp();
+
+  app.oauth_consents.update_one(
+    doc! { "user_id": user_id, "app_id": app_id },
+    doc! {
+      "$addToSet": { "scopes": { "$each": scopes } },
+      "$set": { "updated_on": now },
+      "$setOnInsert": { "_id": ObjectId::new(), "created_on": now }
+    }
+  ).with_options(UpdateOptions::builder().upsert(true).build()).await.unwrap();
+}
+
+pub async fn revoke( app: &AppHandler, user_id: ObjectId, app_id: ObjectId ){
+  app.oauth_consents.delete_one(doc! { "user_id": user_id, "app_id": app_id }).await.unwrap();
+}
\ No newline at end of file
@@ -1,6 +1,7 @@
 use core::str;
 use std::env;
 
+use anyhow::{ anyhow, bail };
 use aes_gcm::{ aead::Aead, AeadCore, Aes256Gcm, Key, KeyInit };
 use base64::prelude::*;
 use rand::rngs::OsRng;
@@ -30,6 +31,52 @@ pub fn get_user_encryption_key( user: &User ) -> [u8; 32]{
   return key;
 }
 
+fn get_signing_key_encryption_key( kid: &str ) -> [u8; 32]{
+  let salt = env::var("ROOT_KEY_SALT").unwrap();
+
+  blake3::Hasher::new_derive_key("id.phazed.xyz signing-key")
+    .update(env::var("ROOT_KEY").unwrap().as_bytes())
+    .update(kid.as_bytes())
+    .update(salt.as_bytes())
+    .finalize()
+    .into()
+}
+
+pub fn get_magic_link_key() -> [u8; 32]{
+  let salt = env::var("ROOT_KEY_SALT").unwrap();
+
+  blake3::Hasher::new_derive_key("id.phazed.xyz magic-link")
+    .update(env::var("ROOT_KEY").unwrap().as_bytes())
+    .update(salt.as_bytes())
+    .finalize()
+    .into()
+}
+
+pub fn encrypt_signing_key( kid: &str, dat: String ) -> String {
+  let key = get_signing_key_encryption_key(kid);
+  let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key));
+
+  let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+
+  let ciphertext = cipher.encrypt(&nonce, dat.as_bytes().as_ref()).unwrap();
+  BASE64_STANDARD.encode([ nonce.as_slice(), ciphertext.as_slice() ].concat())
+}
+
+pub fn decrypt_signing_key( kid: &str, dat: String ) -> anyhow::Result<String> {
+  let key = get_signing_key_encryption_key(kid);
+  let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key));
+
+  let raw = BASE64_STANDARD.decode(dat)?;
+
+  let nonce_length: usize = U12::to_int();
+  if raw.len() < nonce_length { bail!("Invalid signing key") }
+
+  let ( nonce, ciphertext ) = raw.split_at(nonce_length);
+
+  let plaintext = cipher.decrypt(nonce.into(), ciphertext.as_ref()).map_err(| _ | anyhow!("Could not decrypt signing key"))?;
+  Ok(String::from_utf8(plaintext)?)
+}
+
 pub fn encrypt_to_user( user: &User, dat: String ) -> String {
   let key = get_user_encryption_key(user);
   let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key));
@@ -0,0 +1,126 @@
+use anyhow::bail;
+use base64::prelude::*;
+use rsa::{ pkcs1v15::{ Signature, SigningKey, VerifyingKey }, signature::{ SignatureEncoding, Signer, Verifier }, traits::PublicKeyParts, RsaPrivateKey, RsaPublicKey };
+use serde_json::{ json, Value };
+use sha2::Sha256;
+
+use crate::{ apphandler::AppHandler, util::keys };
+
+// typ is "JWT" for ID tokens and "at+jwt" for access tokens (RFC 9068 2.1)
+pub async fn sign( app: &AppHandler, typ: &str, claims: &Value ) -> anyhow::Result<String>{
+  let ( kid, key ) = keys::active(app).await?;
+  encode(&kid, key, typ, claims)
+}
+
+fn encode( kid: &str, key: RsaPrivateKey, typ: &str, claims: &Value ) -> anyhow::Result<String>{
+  let header = json!({ "alg": "RS256", "typ": typ, "kid": kid });
+
+  let payload = format!(
+    "{}.{}",
+    BASE64_URL_SAFE_NO_PAD.encode(serde_json::to_string(&header)?),
+    BASE64_URL_SAFE_NO_PAD.encode(serde_json::to_string(claims)?)
+  );
+
+  let signature = SigningKey::<Sha256>::new(key).sign(payload.as_bytes());
+  Ok(format!("{}.{}", payload, BASE64_URL_SAFE_NO_PAD.encode(signature.to_bytes())))
+}
+
+// Checks the signature and returns the header and claims, expiry and the rest are up to the caller
+pub async fn verify( app: &AppHandler, token: &str ) -> anyhow::Result<( Value, Value )>{
+  let header = header(token)?;
+
+  let Some(kid) = header["kid"].as_str() else { bail!("Invalid Token") };
+  let Some(key) = keys::find(app, kid).await? else { bail!("Invalid Token") };
+
+  Ok(( header, decode(token, key.to_public_key())? ))
+}
+
+// Only RS256, anything else (including "none") is refused before we look for a key
+fn header( token: &str ) -> anyhow::Result<Value>{
+  let parts: Vec<&str> = token.split('.').collect();
+  if parts.len() != 3 { bail!("Invalid Token") }
+
+  let header: Value = serde_json::from_slice(&BASE64_URL_SAFE_NO_PAD.decode(parts[0])?)?;
+  if header["alg"] != "RS256" { bail!("Invalid Token") }
+
+  Ok(header)
+}
+
+fn decode( token: &str, key: RsaPublicKey ) -> anyhow::Result<Value>{
+  let parts: Vec<&str> = token.split('.').collect();
+  if parts.len() != 3 { bail!("Invalid Token") }
+
+  let signature = Signature::try_from(BASE64_URL_SAFE_NO_PAD.decode(parts[2])?.as_slice())?;
+  VerifyingKey::<Sha256>::new(key).verify(format!("{}.{}", parts[0], parts[1]).as_bytes(), &signature)?;
+
+  Ok(serde_json::from_slice(&BASE64_URL_SAFE_NO_PAD.decode(parts[1])?)?)
+}
+
+// Publishes the active key and the retired ones that can still have tokens out there
+pub async fn jwks( app: &AppHandler ) -> anyhow::Result<Value>{
+  let keys: Vec<Value> = keys::load(app).await?.iter().map(| x | {
+    let public_key = x.key.to_public_key();
+
+    json!({
+      "kty": "RSA",
+      "use": "sig",
+      "alg": "RS256",
+      "kid": x.kid,
+      "n": BASE64_URL_SAFE_NO_PAD.encode(public_key.n().to_bytes_be()),
+      "e": BASE64_URL_SAFE_NO_PAD.encode(public_key.e().to_bytes_be())
+    })
+  }).collect();
+
+  Ok(json!({ "keys": keys }))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn key() -> RsaPrivateKey{
+    RsaPrivateKey::new(&mut rand::thread_rng(), 1024).unwrap()
+  }
+
+  fn b64( value: &Value ) -> String{
+    BASE64_URL_SAFE_NO_PAD.encode(value.to_string())
+  }
+
+  #[test]
+  fn round_trip(){
+    let key = key();
+    let token = encode("kid1", key.clone(), "JWT", &json!({ "sub": "123" })).unwrap();
+
+    let header = header(&token).unwrap();
+    assert_eq!(header["kid"], "kid1");
+    assert_eq!(header["typ"], "JWT");
+
+    assert_eq!(decode(&token, key.to_public_key()).unwrap()["sub"], "123");
+  }
+
+  #[test]
+  fn rejects_other_keys(){
+    let token = encode("kid1", key(), "JWT", &json!({ "sub": "123" })).unwrap();
+    assert!(decode(&token, key().to_public_key()).is_err());
+  }
+
+  #[test]
+  fn rejects_tampered_claims(){
+    let key = key();
+    let token = encode("kid1", key.clone(), "JWT", &json!({ "sub": "123" })).unwrap();
+
+    let parts: Vec<&str> = token.split('.').collect();
+    let tampered = format!("{}.{}.{}", parts[0], b64(&json!({ "sub": "456" })), parts[2]);
+
+    assert!(decode(&tampered, key.to_public_key()).is_err());
+  }
+
+  #[test]
+  fn rejects_other_algorithms(){
+    let claims = b64(&json!({ "sub": "123" }));
+
+    assert!(header(&format!("{}.{}.", b64(&json!({ "alg": "none", "kid": "kid1" })), claims)).is_err());
+    assert!(header(&format!("{}.{}.sig", b64(&json!({ "alg": "HS256", "kid": "kid1" })), claims)).is_err());
+    assert!(header(&format!("{}.{}", b64(&json!({ "alg": "RS256", "kid": "kid1" })), claims)).is_err());
+  }
+}
\ No newline at end of file
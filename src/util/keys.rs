@@ -0,0 +1,173 @@
+use std::{ env, sync::Arc, time::Duration };
+
+use anyhow::bail;
+use base64::prelude::*;
+use bson::{ doc, oid::ObjectId };
+use chrono::Utc;
+use rsa::{ pkcs8::{ DecodePrivateKey, EncodePrivateKey, EncodePublicKey, LineEnding }, RsaPrivateKey };
+use sha2::{ Digest, Sha256 };
+
+use crate::{ apphandler::AppHandler, structs::signingkey::SigningKeyPair, util::{ encrypt, oidc } };
+
+pub const ROTATION_INTERVAL: i64 = 2592000; // 30 days
+
+// How long other instances can go on using keys they loaded before a rotation
+const CACHE_LIFETIME: i64 = 60;
+
+// Unknown key IDs only trigger a reload once the cache is at least this old, anyone can send a made up one
+const RELOAD_FLOOR: i64 = 5;
+
+#[derive(Debug)]
+pub struct LoadedKey{
+  pub kid: String,
+  pub key: RsaPrivateKey,
+  pub active: bool
+}
+
+// Key ID is derived from the public key so it changes whenever the key does
+fn key_id( key: &RsaPrivateKey ) -> anyhow::Result<String>{
+  let der = key.to_public_key().to_public_key_der()?;
+  Ok(BASE64_URL_SAFE_NO_PAD.encode(&Sha256::digest(der.as_bytes())[..12]))
+}
+
+// The active key and any retired keys that still have tokens out there
+pub async fn load( app: &AppHandler ) -> anyhow::Result<Arc<Vec<LoadedKey>>>{
+  let now = Utc::now().timestamp();
+
+  if let Some(( loaded_on, keys )) = &*app.key_cache.read().await {
+    if loaded_on + CACHE_LIFETIME > now { return Ok(keys.clone()) }
+  }
+
+  reload(app).await
+}
+
+pub async fn reload( app: &AppHandler ) -> anyhow::Result<Arc<Vec<LoadedKey>>>{
+  let now = Utc::now().timestamp();
+
+  let mut cursor = app.signing_keys.find(doc! { "$or": [ { "active": true }, { "verify_until": { "$gt": now } } ] }).await?;
+  let mut keys = Vec::new();
+
+  while cursor.advance().await? {
+    let stored = cursor.deserialize_current()?;
+    let pem = encrypt::decrypt_signing_key(&stored.kid, stored.private_key)?;
+
+    keys.push(LoadedKey { kid: stored.kid, key: RsaPrivateKey::from_pkcs8_pem(&pem)?, active: stored.active });
+  }
+
+  let keys = Arc::new(keys);
+  *app.key_cache.write().await = Some(( now, keys.clone() ));
+
+  Ok(keys)
+}
+
+pub async fn find( app: &AppHandler, kid: &str ) -> anyhow::Result<Option<RsaPrivateKey>>{
+  let find = | keys: &[LoadedKey] | keys.iter().find(| x | x.kid == kid).map(| x | x.key.clone());
+  if let Some(key) = find(&load(app).await?) { return Ok(Some(key)) }
+
+  // Another instance might have just rotated, so check again before giving up
+  let now = Utc::now().timestamp();
+  if app.key_cache.read().await.as_ref().is_some_and(| ( loaded_on, _ ) | loaded_on + RELOAD_FLOOR > now) { return Ok(None) }
+
+  Ok(find(&reload(app).await?))
+}
+
+pub async fn active( app: &AppHandler ) -> anyhow::Result<( String, RsaPrivateKey )>{
+  let keys = load(app).await?;
+
+  let Some(key) = keys.iter().find(| x | x.active) else { bail!("No active signing key") };
+  Ok(( key.kid.clone(), key.key.clone() ))
+}
+
+async fn store( app: &AppHandler, key: RsaPrivateKey ) -> anyhow::Result<String>{
+  let now = Utc::now().timestamp();
+
+  let kid = key_id(&key)?;
+  let pem = key.to_pkcs8_pem(LineEnding::LF)?.to_string();
+
+  let stored = SigningKeyPair {
+    _id: ObjectId::new(),
+    kid: kid.clone(),
+
+    private_key: encrypt::encrypt_signing_key(&kid, pem),
+
+    created_on: now,
+    active: true,
+
+    retired_on: None,
+    verify_until: None
+  };
+
+  let current = app.signing_keys.find_one(doc! { "active": true }).await?;
+
+  // New key goes in first so there's never a moment without an active one
+  app.signing_keys.insert_one(&stored).await?;
+
+  // Every instance runs the worker, only the one that gets to retire the current key keeps its new one.
+  // Old keys stay published until the longest lived token they could have signed runs out, plus however long
+  // other instances might still be signing with them from their cache
+  let won = match current {
+    Some(current) => app.signing_keys.find_one_and_update(doc! { "_id": current._id, "active": true }, doc! {
+      "$set": { "active": false, "retired_on": now, "verify_until": now + oidc::ID_TOKEN_LIFETIME + CACHE_LIFETIME }
+    }).await?.is_some(),
+
+    // Nothing to swap with on the very first key, the oldest one inserted wins
+    None => app.signing_keys.find_one(doc! { "active": true }).sort(doc! { "_id": 1 }).await?.is_some_and(| x | x._id == stored._id)
+  };
+
+  // Something could have signed with the losing key while it was briefly active, so it gets retired rather than deleted
+  if !won {
+    app.signing_keys.update_one(doc! { "_id": stored._id }, doc! {
+      "$set": { "active": false, "retired_on": now, "verify_until": now + oidc::ID_TOKEN_LIFETIME + CACHE_LIFETIME }
+    }).await?;
+
+    reload(app).await?;
+
+    return Ok(active(app).await?.0)
+  }
+
+  app.signing_keys.delete_many(doc! { "active": false, "verify_until": { "$lt": now } }).await?;
+
+  reload(app).await?;
+  Ok(kid)
+}
+
+pub async fn rotate( app: &AppHandler ) -> anyhow::Result<String>{
+  let key = tokio::task::spawn_blocking(|| RsaPrivateKey::new(&mut rand::thread_rng(), 2048)).await??;
+  store(app, key).await
+}
+
+// Called on startup, makes sure there's a key to sign with
+pub async fn ensure( app: &AppHandler ) -> anyhow::Result<()>{
+  if app.signing_keys.find_one(doc! { "active": true }).await?.is_some(){ return Ok(()) }
+
+  // Carry over the key from before keys were stored, so tokens it signed keep working
+  if let Ok(pem) = env::var("OIDC_SIGNING_KEY") {
+    store(app, RsaPrivateKey::from_pkcs8_pem(&pem)?).await?;
+  } else{
+    rotate(app).await?;
+  }
+
+  Ok(())
+}
+
+// Background task started in main, rotates the active key once it gets old
+pub async fn worker( app: Arc<AppHandler> ){
+  loop {
+    tokio::time::sleep(Duration::from_secs(3600)).await;
+
+    let now = Utc::now().timestamp();
+
+    // Runs for as long as the server does, so a database hiccup just waits for the next check
+    let active = match app.signing_keys.find_one(doc! { "active": true }).await {
+      Ok(active) => active,
+      Err(err) => {
+        log::error!("Failed to check signing key: {}", err);
+        continue
+      }
+    };
+
+    if active.is_none_or(| x | x.created_on + ROTATION_INTERVAL < now) {
+      if let Err(err) = rotate(&app).await { log::error!("Failed to rotate signing key: {}", err); }
+    }
+  }
+}
\ No newline at end of file
@@ -36,26 +36,7 @@ pub async fn try_login( ip: &str, username: String, password: String, remote_pub
 
   let user = user.unwrap();
   
-  if user.account_locked{
-    if user.locked_until < Utc::now().timestamp(){
-      app.users.update_one(doc! { "_id": user._id }, doc! { "$set": { "account_locked": false } }).await?;
-    } else{
-      // 1 - Error, 0 - Error Code "Account locked until 000"
-      ws.send(Message::Text(encrypt(format!("12{}", user.locked_until.to_string()), &remote_pub_key)?.into())).await?;
-      bail!("Account locked until 000");
-    }
-  }
-
-  if user.login_attempts > 4{
-    let locked_until = Utc::now().timestamp() + 900;
-    app.users.update_one(doc! { "_id": user._id }, doc! { 
-      "$set": {
-        "account_locked": true,
-        "locked_until": locked_until,
-        "login_attempts": 0
-      }
-    }).await?;
-
+  if let Some(locked_until) = locked_until(&user, &app).await?{
     // 1 - Error, 0 - Error Code "Account locked until 000"
     ws.send(Message::Text(encrypt(format!("12{}", locked_until.to_string()), &remote_pub_key)?.into())).await?;
     bail!("Account locked until 000");
@@ -64,14 +45,54 @@ pub async fn try_login( ip: &str, username: String, password: String, remote_pub
   let argon2 = Argon2::default();
 
   let pass = argon2.verify_password(password.as_bytes(), &PasswordHash::parse(&user.password, Encoding::B64).unwrap());
+  // Repeated failures are throttled per IP and username by the tunnel, see util::ratelimit
   if pass.is_err(){
-    app.users.update_one(doc! { "_id": user._id }, doc! { "$inc": { "login_attempts": 1 } }).await.unwrap();
+    // 1 - Error, 0 - Error Code "Incorrect Username or Password"
+    ws.send(Message::Text(encrypt("11".to_owned(), &remote_pub_key)?.into())).await?;
+    bail!("Incorrect Username or Password");
+  }
 
+  if is_deleted(&user){
     // 1 - Error, 0 - Error Code "Incorrect Username or Password"
     ws.send(Message::Text(encrypt("11".to_owned(), &remote_pub_key)?.into())).await?;
     bail!("Incorrect Username or Password");
   }
 
+  let ( token, session ) = create_session(ip, &user, false, &app).await?;
+  app.users.update_one(doc! { "_id": user._id }, doc! { "$set": { "login_attempts": 0 } }).await.unwrap();
+
+  ws.send(Message::Text(encrypt(format!("0{}{}", token, session._id), &remote_pub_key)?.into())).await?;
+
+  // email::send(
+  //   ( user.username.as_str(), user.email.as_str() ),
+  //   "PhazeID Login",
+  //   &fs::read_to_string("templates/email/login_alert.html").unwrap()
+  //     .replace("{{USERNAME}}", &user.username)
+  //     .replace("{{IP}}", &session.loc.ip)
+  // ).await.unwrap();
+
+  Ok(user)
+}
+
+// Some(timestamp) while the account is locked, clears the lock once it's run out
+pub async fn locked_until( user: &User, app: &AppHandler ) -> anyhow::Result<Option<i64>>{
+  if !user.account_locked { return Ok(None) }
+
+  if user.locked_until < Utc::now().timestamp(){
+    app.users.update_one(doc! { "_id": user._id }, doc! { "$set": { "account_locked": false } }).await?;
+    Ok(None)
+  } else{
+    Ok(Some(user.locked_until))
+  }
+}
+
+// Accounts past their deletion date can't be logged into anymore
+pub fn is_deleted( user: &User ) -> bool{
+  user.deletion_flagged_after.is_some_and(| deleting_at | deleting_at < Utc::now().timestamp())
+}
+
+// Shared by every way of logging in, sessions start out unverified unless the login already counts as a second factor
+pub async fn create_session( ip: &str, user: &User, valid: bool, app: &AppHandler ) -> anyhow::Result<( String, Session )>{
   let ip_info = reqwest::get(format!("https://ipinfo.io/{}?token={}", ip, env::var("IPINFO_KEY").unwrap())).await.unwrap();
   let ip_info: IPInfo = serde_json::from_str(&ip_info.text().await.unwrap()).unwrap();
 
@@ -81,15 +102,7 @@ pub async fn try_login( ip: &str, username: String, password: String, remote_pub
   app.sessions.delete_many(doc! { "valid": false, "user_id": user._id }).await.unwrap();
   app.sessions.delete_many(doc! { "expires_on": { "$lt": now }, "user_id": user._id }).await.unwrap();
 
-  if user.deletion_flagged_after.is_some(){
-    let deleting_at = user.deletion_flagged_after.unwrap();
-    if deleting_at < now {
-      // 1 - Error, 0 - Error Code "Incorrect Username or Password"
-      ws.send(Message::Text(encrypt("11".to_owned(), &remote_pub_key)?.into())).await?;
-      bail!("Incorrect Username or Password");
-    }
-  }
-
+  let argon2 = Argon2::default();
   let salt = SaltString::generate(&mut OsRng);
 
   let session = Session {
@@ -102,24 +115,14 @@ pub async fn try_login( ip: &str, username: String, password: String, remote_pub
 
     loc: ip_info,
 
-    valid: false,
+    valid,
     challenge_code: None,
 
-    user_id: user._id
+    user_id: user._id,
+    elevated_until: 0
   };
 
   app.sessions.insert_one(&session).await.unwrap();
-  app.users.update_one(doc! { "_id": user._id }, doc! { "$set": { "login_attempts": 0 } }).await.unwrap();
 
-  ws.send(Message::Text(encrypt(format!("0{}{}", token, session._id), &remote_pub_key)?.into())).await?;
-
-  // email::send(
-  //   ( user.username.as_str(), user.email.as_str() ),
-  //   "PhazeID Login",
-  //   &fs::read_to_string("templates/email/login_alert.html").unwrap()
-  //     .replace("{{USERNAME}}", &user.username)
-  //     .replace("{{IP}}", &session.loc.ip)
-  // ).await.unwrap();
-
-  Ok(user)
+  Ok(( token, session ))
 }
\ No newline at end of file
@@ -0,0 +1,58 @@
+use base64::{ prelude::BASE64_URL_SAFE_NO_PAD, Engine };
+use bson::{ doc, oid::ObjectId };
+use chrono::Utc;
+use hmac::{ Hmac, Mac };
+use sha2::Sha256;
+
+use crate::{ apphandler::AppHandler, structs::{ magiclink::MagicLink, user::User }, util::encrypt };
+
+pub const LINK_LIFETIME: i64 = 900;
+pub const RESEND_COOLDOWN: i64 = 60;
+
+fn mac( link_id: ObjectId, user_id: ObjectId, expires_on: i64 ) -> Hmac<Sha256>{
+  let mut mac = Hmac::<Sha256>::new_from_slice(&encrypt::get_magic_link_key()).unwrap();
+  mac.update(format!("{}.{}.{}", link_id, user_id, expires_on).as_bytes());
+
+  mac
+}
+
+// Returns the token to put in the link, None if one was sent too recently
+pub async fn create( app: &AppHandler, user: &User, ip: Option<String> ) -> Option<String>{
+  let now = Utc::now().timestamp();
+  app.magic_links.delete_many(doc! { "expires_on": { "$lt": now } }).await.unwrap();
+
+  let recent = app.magic_links.count_documents(doc! { "user_id": user._id, "created_on": { "$gt": now - RESEND_COOLDOWN } }).await.unwrap();
+  if recent > 0 { return None }
+
+  let link = MagicLink {
+    _id: ObjectId::new(),
+    user_id: user._id,
+
+    ip,
+
+    created_on: now,
+    expires_on: now + LINK_LIFETIME
+  };
+
+  app.magic_links.insert_one(&link).await.unwrap();
+
+  let signature = mac(link._id, link.user_id, link.expires_on).finalize().into_bytes();
+  Some(format!("{}.{}", link._id, BASE64_URL_SAFE_NO_PAD.encode(signature)))
+}
+
+// Checks the signature before burning the link, so a guessed ID can't be used to invalidate someone else's
+pub async fn redeem( app: &AppHandler, token: &str ) -> Option<ObjectId>{
+  let ( link_id, signature ) = token.split_once('.')?;
+
+  let link_id = ObjectId::parse_str(link_id).ok()?;
+  let signature = BASE64_URL_SAFE_NO_PAD.decode(signature).ok()?;
+
+  let now = Utc::now().timestamp();
+
+  let link = app.magic_links.find_one(doc! { "_id": link_id, "expires_on": { "$gt": now } }).await.unwrap()?;
+  mac(link._id, link.user_id, link.expires_on).verify_slice(&signature).ok()?;
+
+  // Whoever deletes it first gets to use it
+  let link = app.magic_links.find_one_and_delete(doc! { "_id": link._id }).await.unwrap()?;
+  Some(link.user_id)
+}
\ No newline at end of file
@@ -8,4 +8,16 @@ pub mod avatar;
 pub mod token;
 pub mod cookies;
 pub mod change_password;
-pub mod ip;
\ No newline at end of file
+pub mod ip;
+pub mod pkce;
+pub mod jwt;
+pub mod oidc;
+pub mod scopes;
+pub mod webhook;
+pub mod redirect_uri;
+pub mod keys;
+pub mod consent;
+pub mod webauthn;
+pub mod magic_link;
+pub mod upstream;
+pub mod ratelimit;
\ No newline at end of file
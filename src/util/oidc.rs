@@ -0,0 +1,51 @@
+use chrono::Utc;
+use bson::oid::ObjectId;
+use serde_json::json;
+
+use crate::{ apphandler::AppHandler, structs::user::User };
+use super::jwt;
+
+pub const ISSUER: &str = "https://idapi-jye3bcyp.phazed.xyz";
+pub const AUTHORIZATION_ENDPOINT: &str = "https://id.phazed.xyz/oauth";
+
+pub const ID_TOKEN_LIFETIME: i64 = 3600;
+pub const ACCESS_TOKEN_LIFETIME: i64 = 900;
+
+pub async fn id_token( app: &AppHandler, user: &User, scopes: &[String], client_id: String, nonce: Option<String> ) -> anyhow::Result<String>{
+  let now = Utc::now().timestamp();
+
+  let mut claims = json!({
+    "iss": ISSUER,
+    "sub": user._id.to_hex(),
+    "aud": client_id,
+    "iat": now,
+    "exp": now + ID_TOKEN_LIFETIME
+  });
+
+  if scopes.contains(&"identify".into()){ claims["preferred_username"] = user.username.clone().into(); }
+
+  if scopes.contains(&"email".into()){
+    claims["email"] = user.email.clone().into();
+    claims["email_verified"] = user.email_verified.into();
+  }
+
+  if let Some(nonce) = nonce { claims["nonce"] = nonce.into(); }
+
+  jwt::sign(app, "JWT", &claims).await
+}
+
+// Short lived so there's no need for revocation checks, jti is the OAuthSession it was issued with
+pub async fn access_token( app: &AppHandler, user_id: ObjectId, scopes: &[String], client_id: String, session_id: ObjectId ) -> anyhow::Result<String>{
+  let now = Utc::now().timestamp();
+
+  jwt::sign(app, "at+jwt", &json!({
+    "iss": ISSUER,
+    "sub": user_id.to_hex(),
+    "aud": client_id,
+    "client_id": client_id,
+    "scope": scopes.join(" "),
+    "iat": now,
+    "exp": now + ACCESS_TOKEN_LIFETIME,
+    "jti": session_id.to_hex()
+  })).await
+}
\ No newline at end of file
@@ -0,0 +1,65 @@
+use base64::prelude::*;
+use sha2::{ Digest, Sha256 };
+
+pub const METHODS: [ &str; 2 ] = [ "S256", "plain" ];
+
+// RFC 7636 4.1: 43 - 128 characters of [A-Z] / [a-z] / [0-9] / "-" / "." / "_" / "~"
+pub fn is_valid( value: &str ) -> bool{
+  value.len() >= 43 &&
+  value.len() <= 128 &&
+  value.chars().all(| c | c.is_ascii_alphanumeric() || c == '-' || c == '.' || c == '_' || c == '~')
+}
+
+pub fn challenge( verifier: &str ) -> String{
+  BASE64_URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()))
+}
+
+pub fn verify( challenge: &str, method: &str, verifier: &str ) -> bool{
+  if !is_valid(verifier) { return false }
+
+  match method{
+    "S256" => self::challenge(verifier) == challenge,
+    "plain" => verifier == challenge,
+    _ => false
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // RFC 7636 Appendix B
+  const VERIFIER: &str = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+  const CHALLENGE: &str = "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM";
+
+  #[test]
+  fn rfc_vector(){
+    assert_eq!(challenge(VERIFIER), CHALLENGE);
+    assert!(verify(CHALLENGE, "S256", VERIFIER));
+  }
+
+  #[test]
+  fn rejects_wrong_verifier(){
+    assert!(!verify(CHALLENGE, "S256", "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXl"));
+    assert!(!verify(CHALLENGE, "S512", VERIFIER));
+    assert!(!verify(CHALLENGE, "plain", VERIFIER));
+  }
+
+  #[test]
+  fn plain(){
+    assert!(verify(VERIFIER, "plain", VERIFIER));
+  }
+
+  #[test]
+  fn verifier_format(){
+    assert!(is_valid(VERIFIER));
+    assert!(is_valid(&"a".repeat(128)));
+
+    assert!(!is_valid(&"a".repeat(42)));
+    assert!(!is_valid(&"a".repeat(129)));
+    assert!(!is_valid(&format!("{}+", "a".repeat(43))));
+
+    // Too short verifiers never pass, even if they match
+    assert!(!verify("short", "plain", "short"));
+  }
+}
\ No newline at end of file
@@ -0,0 +1,119 @@
+use std::time::Duration;
+
+use bson::{ doc, oid::ObjectId, DateTime };
+use chrono::Utc;
+use mongodb::{ options::IndexOptions, IndexModel };
+
+use crate::{ apphandler::AppHandler, structs::ratelimit::RateLimitHit };
+
+// Attempts within the window past `free` have to wait a growing delay after the one before, anything past `max`
+// is refused until the window slides
+pub struct Limit{
+  pub name: &'static str,
+  pub window: i64,
+  pub free: u64,
+  pub max: Option<u64>
+}
+
+// Login limits on just a username are never hard, anyone could use those up and lock the owner out. Emails to an
+// address get a cap anyway, the owner waiting an hour is better than their inbox being flooded
+pub const LOGIN_IP: Limit = Limit { name: "login_ip", window: 3600, free: 20, max: Some(100) };
+pub const LOGIN_USERNAME: Limit = Limit { name: "login_username", window: 3600, free: 5, max: None };
+pub const LOGIN_IP_USERNAME: Limit = Limit { name: "login_ip_username", window: 900, free: 3, max: Some(10) };
+
+pub const SIGNUP_IP: Limit = Limit { name: "signup_ip", window: 3600, free: 3, max: Some(10) };
+
+// Shared by everything that emails a link (password resets, magic links), see `email_limits`
+pub const EMAIL_IP: Limit = Limit { name: "email_ip", window: 3600, free: 5, max: Some(20) };
+pub const EMAIL_ADDRESS: Limit = Limit { name: "email_address", window: 3600, free: 1, max: Some(3) };
+pub const EMAIL_IP_ADDRESS: Limit = Limit { name: "email_ip_address", window: 900, free: 1, max: Some(3) };
+
+pub const NEW_PASSWORD_IP: Limit = Limit { name: "new_password_ip", window: 3600, free: 5, max: Some(20) };
+
+// Device flow user codes are short enough to guess (RFC 8628 5.1)
+pub const USER_CODE_IP: Limit = Limit { name: "user_code_ip", window: 3600, free: 10, max: Some(30) };
+pub const USER_CODE_SESSION: Limit = Limit { name: "user_code_session", window: 900, free: 5, max: Some(15) };
+
+// Per session, so a stolen cookie guessing passwords can't use up the owner's attempts from their own sessions
+pub const REAUTH_SESSION: Limit = Limit { name: "reauth_session", window: 900, free: 3, max: Some(10) };
+
+const MAX_DELAY: i64 = 10;
+
+pub fn email_limits( ip: &str, email: &str ) -> [ ( &'static Limit, String ); 3 ]{
+  [
+    ( &EMAIL_IP, ip.to_owned() ),
+    ( &EMAIL_ADDRESS, email.to_owned() ),
+    ( &EMAIL_IP_ADDRESS, format!("{}:{}", ip, email) )
+  ]
+}
+
+// Keys are hashed so we aren't keeping a list of every email and IP that tried something
+fn key( limit: &Limit, value: &str ) -> String{
+  format!("{}:{}", limit.name, blake3::hash(value.to_lowercase().as_bytes()).to_hex())
+}
+
+// Hits carry a date for the TTL index, so keys nobody touches again still get cleaned up
+pub async fn create_indexes( app: &AppHandler ) -> anyhow::Result<()>{
+  app.rate_limits.create_index(IndexModel::builder().keys(doc! { "key": 1, "created_on": 1 }).build()).await?;
+  app.rate_limits.create_index(IndexModel::builder()
+    .keys(doc! { "expires_at": 1 })
+    .options(IndexOptions::builder().expire_after(Duration::from_secs(0)).build())
+    .build()).await?;
+
+  Ok(())
+}
+
+// Records the attempt before it's made, so parallel attempts all count each other. Returns when they can try
+// again if a limit is used up or the last attempt was too recent. The delay is checked against the stored hits
+// rather than slept out, so opening more connections doesn't get around it
+pub async fn begin( app: &AppHandler, limits: &[( &Limit, String )] ) -> Result<Vec<ObjectId>, i64>{
+  let now = Utc::now().timestamp();
+
+  let hits: Vec<RateLimitHit> = limits.iter().map(| ( limit, value ) | RateLimitHit {
+    _id: ObjectId::new(),
+    key: key(limit, value),
+
+    created_on: now,
+    expires_at: DateTime::from_millis(( now + limit.window ) * 1000)
+  }).collect();
+
+  let ids: Vec<ObjectId> = hits.iter().map(| x | x._id).collect();
+  app.rate_limits.insert_many(hits).await.unwrap();
+
+  let mut retry_at = None;
+
+  for ( limit, value ) in limits {
+    let filter = doc! { "key": key(limit, value), "created_on": { "$gt": now - limit.window } };
+    let count = app.rate_limits.count_documents(filter.clone()).await.unwrap();
+
+    if limit.max.is_some_and(| max | count > max) {
+      let oldest = app.rate_limits.find_one(filter).sort(doc! { "created_on": 1 }).await.unwrap();
+      let until = oldest.map(| x | x.created_on + limit.window).unwrap_or(now + limit.window);
+
+      retry_at = retry_at.max(Some(until));
+    } else if count > limit.free {
+      let delay = ( 1i64 << ( count - limit.free - 1 ).min(8) ).min(MAX_DELAY);
+
+      let mut filter = filter;
+      filter.insert("_id", doc! { "$nin": &ids });
+
+      let last = app.rate_limits.find_one(filter).sort(doc! { "created_on": -1 }).await.unwrap();
+      let until = last.map(| x | x.created_on + delay).unwrap_or(now);
+
+      if until > now { retry_at = retry_at.max(Some(until)); }
+    }
+  }
+
+  // Refused attempts don't count, otherwise hammering away would keep pushing the window out
+  if let Some(retry_at) = retry_at {
+    forget(app, ids).await;
+    return Err(retry_at)
+  }
+
+  Ok(ids)
+}
+
+// For attempts that turned out fine and shouldn't count
+pub async fn forget( app: &AppHandler, ids: Vec<ObjectId> ){
+  app.rate_limits.delete_many(doc! { "_id": { "$in": ids } }).await.unwrap();
+}
\ No newline at end of file
@@ -0,0 +1,40 @@
+// Scope name and the description shown on the consent screen
+pub const SCOPES: [ ( &str, &str ); 6 ] = [
+  ( "identify", "See your username and account ID" ),
+  ( "openid", "Sign you in with your PhazeID" ),
+  ( "email", "See your email address" ),
+  ( "avatar", "See your avatar" ),
+  ( "roles", "See your account roles" ),
+  ( "patreon", "See your linked Patreon account and tiers" )
+];
+
+// What an app gets asked about when it doesn't say which scopes it wants
+pub const DEFAULT_SCOPE: &str = "identify";
+
+// Scopes an app can request for itself with the client_credentials grant
+pub const APP_SCOPES: [ ( &str, &str ); 1 ] = [
+  ( "deletion_queue", "Read and manage the data deletion queue" )
+];
+
+pub fn is_valid( scope: &str ) -> bool{
+  SCOPES.iter().any(| x | x.0 == scope)
+}
+
+pub fn is_valid_app( scope: &str ) -> bool{
+  APP_SCOPES.iter().any(| x | x.0 == scope)
+}
+
+pub fn describe( scope: &str ) -> Option<&'static str>{
+  SCOPES.iter().find(| x | x.0 == scope).map(| x | x.1)
+}
+
+// Scopes are space separated in the spec, but we've always taken commas so allow both
+pub fn parse( scope: &str ) -> Vec<String>{
+  let mut scopes: Vec<String> = vec![];
+
+  for scope in scope.split([ ',', ' ' ]).filter(| x | !x.is_empty()) {
+    if !scopes.iter().any(| x | x == scope) { scopes.push(scope.to_owned()); }
+  }
+
+  scopes
+}
\ No newline at end of file
@@ -15,36 +15,61 @@ use super::{ email, encrypt::encrypt };
 
 const DEFAULT_AVIS: [&str; 1] = [ "default" ];
 
-pub async fn try_signup( ip: &str, username: String, password: String, email: String, remote_pub_key: &RsaPublicKey, ws: &mut WebSocket, app: Arc<AppHandler> ) -> anyhow::Result<User>{
-  if
-    username.eq("") ||
-    username.len() > 50 ||
-    password.len() > 50
-  {
-    // 1 - Error, 0 - Error Code "Password and Username must be less than 50 characters"
-    ws.send(Message::Text(encrypt("10".to_owned(), &remote_pub_key).unwrap().into())).await.unwrap();
-    bail!("Password and Username must be less than 50 characters");
+// Anything that stops an account from being made, shared by every way of signing up
+pub enum SignupError{
+  InvalidUsername,
+  InvalidEmail,
+  UsernameInUse,
+  EmailInUse
+}
+
+impl SignupError{
+  // Error codes the tunnel sends back for AS
+  pub fn code( &self ) -> &'static str{
+    match self {
+      SignupError::InvalidUsername => "10",
+      SignupError::InvalidEmail => "11",
+      SignupError::UsernameInUse => "12",
+      SignupError::EmailInUse => "13"
+    }
   }
 
-  let regex = Regex::new(r"^([^\x00-\x20\x22\x28\x29\x2c\x2e\x3a-\x3c\x3e\x40\x5b-\x5d\x7f-\xff]+|\x22([^\x0d\x22\x5c\x80-\xff]|\x5c[\x00-\x7f])*\x22)(\x2e([^\x00-\x20\x22\x28\x29\x2c\x2e\x3a-\x3c\x3e\x40\x5b-\x5d\x7f-\xff]+|\x22([^\x0d\x22\x5c\x80-\xff]|\x5c[\x00-\x7f])*\x22))*\x40([^\x00-\x20\x22\x28\x29\x2c\x2e\x3a-\x3c\x3e\x40\x5b-\x5d\x7f-\xff]+|\x5b([^\x0d\x5b-\x5d\x80-\xff]|\x5c[\x00-\x7f])*\x5d)(\x2e([^\x00-\x20\x22\x28\x29\x2c\x2e\x3a-\x3c\x3e\x40\x5b-\x5d\x7f-\xff]+|\x5b([^\x0d\x5b-\x5d\x80-\xff]|\x5c[\x00-\x7f])*\x5d))*$").unwrap();
-  if !regex.is_match(&email){
-    // 1 - Error, 1 - Error Code "Invalid Email"
-    ws.send(Message::Text(encrypt("11".to_owned(), &remote_pub_key).unwrap().into())).await.unwrap();
-    bail!("Invalid Email");
+  pub fn message( &self ) -> &'static str{
+    match self {
+      SignupError::InvalidUsername => "Password and Username must be less than 50 characters",
+      SignupError::InvalidEmail => "Invalid Email",
+      SignupError::UsernameInUse => "Username in Use",
+      SignupError::EmailInUse => "Email in Use"
+    }
   }
+}
+
+pub async fn check( username: &str, email: &str, app: &AppHandler ) -> Result<(), SignupError>{
+  if username.is_empty() || username.len() > 50 { return Err(SignupError::InvalidUsername) }
+
+  let regex = Regex::new(r"^([^\x00-\x20\x22\x28\x29\x2c\x2e\x3a-\x3c\x3e\x40\x5b-\x5d\x7f-\xff]+|\x22([^\x0d\x22\x5c\x80-\xff]|\x5c[\x00-\x7f])*\x22)(\x2e([^\x00-\x20\x22\x28\x29\x2c\x2e\x3a-\x3c\x3e\x40\x5b-\x5d\x7f-\xff]+|\x22([^\x0d\x22\x5c\x80-\xff]|\x5c[\x00-\x7f])*\x22))*\x40([^\x00-\x20\x22\x28\x29\x2c\x2e\x3a-\x3c\x3e\x40\x5b-\x5d\x7f-\xff]+|\x5b([^\x0d\x5b-\x5d\x80-\xff]|\x5c[\x00-\x7f])*\x5d)(\x2e([^\x00-\x20\x22\x28\x29\x2c\x2e\x3a-\x3c\x3e\x40\x5b-\x5d\x7f-\xff]+|\x5b([^\x0d\x5b-\x5d\x80-\xff]|\x5c[\x00-\x7f])*\x5d))*$").unwrap();
+  if !regex.is_match(email){ return Err(SignupError::InvalidEmail) }
+
+  let user = app.users.find_one(doc! { "username": username }).await.unwrap();
+  if user.is_some(){ return Err(SignupError::UsernameInUse) }
+
+  let user = app.users.find_one(doc! { "email": email }).await.unwrap();
+  if user.is_some(){ return Err(SignupError::EmailInUse) }
 
-  let user = app.users.find_one(doc! { "username": &username }).await.unwrap();
-  if user.is_some(){
-    // 1 - Error, 2 - Error Code "Username in Use"
-    ws.send(Message::Text(encrypt("12".to_owned(), &remote_pub_key).unwrap().into())).await.unwrap();
-    bail!("Username in Use");
+  Ok(())
+}
+
+pub async fn try_signup( ip: &str, username: String, password: String, email: String, remote_pub_key: &RsaPublicKey, ws: &mut WebSocket, app: Arc<AppHandler> ) -> anyhow::Result<User>{
+  if password.len() > 50 {
+    // 1 - Error, 0 - Error Code "Password and Username must be less than 50 characters"
+    ws.send(Message::Text(encrypt("10".to_owned(), &remote_pub_key).unwrap().into())).await.unwrap();
+    bail!("Password and Username must be less than 50 characters");
   }
 
-  let user = app.users.find_one(doc! { "email": &email }).await.unwrap();
-  if user.is_some(){
-    // 1 - Error, 3 - Error Code "Email in Use"
-    ws.send(Message::Text(encrypt("13".to_owned(), &remote_pub_key).unwrap().into())).await.unwrap();
-    bail!("Email in Use");
+  if let Err(err) = check(&username, &email, &app).await {
+    // 1 - Error, 0/1/2/3 - Error Code
+    ws.send(Message::Text(encrypt(err.code().to_owned(), &remote_pub_key).unwrap().into())).await.unwrap();
+    bail!(err.message());
   }
 
   let ip_info = reqwest::get(format!("https://ipinfo.io/{}?token={}", ip, env::var("IPINFO_KEY").unwrap())).await.unwrap();
@@ -58,7 +83,43 @@ pub async fn try_signup( ip: &str, username: String, password: String, email: St
   let token: String = rand::thread_rng().sample_iter(&Alphanumeric).take(64).map(char::from).collect();
   let now = Utc::now().timestamp();
 
-  let user = User {
+  let user = new_user(username, password_hash, email);
+
+  let session = Session {
+    _id: ObjectId::new(),
+
+    token: argon2.hash_password(token.as_bytes(), &salt).unwrap().to_string(),
+
+    created_on: now,
+    expires_on: now + 2629800, // Session expires in a month
+
+    loc: ip_info,
+
+    valid: false,
+    challenge_code: None,
+
+    user_id: user._id,
+    elevated_until: 0
+  };
+
+  email::send(
+    ( user.username.as_str(), user.email.as_str() ), 
+    "Welcome to PhazeID",
+    &fs::read_to_string("templates/email/signup_verification.html").unwrap()
+      .replace("{{USERNAME}}", &user.username)
+      .replace("{{CODE}}", &user.email_verification_code)
+  ).await.unwrap();
+
+  app.users.insert_one(&user).await.unwrap();
+  app.sessions.insert_one(&session).await.unwrap();
+
+  // 0 - No Error
+  ws.send(Message::Text(encrypt(format!("0{}{}", token, session._id), &remote_pub_key).unwrap().into())).await.unwrap();
+  Ok(user)
+}
+
+pub fn new_user( username: String, password_hash: String, email: String ) -> User{
+  User {
     _id: ObjectId::new(),
 
     username,
@@ -86,6 +147,8 @@ pub async fn try_signup( ip: &str, username: String, password: String, email: St
     has_mfa: false,
     mfa_string: None,
     backup_codes: vec![],
+    passkeys: vec![],
+    linked_identities: vec![],
 
     roles: vec![],
 
@@ -100,36 +163,5 @@ pub async fn try_signup( ip: &str, username: String, password: String, email: St
 
     deletion_flagged_after: None,
     apps_to_delete_data: vec![]
-  };
-
-  let session = Session {
-    _id: ObjectId::new(),
-
-    token: argon2.hash_password(token.as_bytes(), &salt).unwrap().to_string(),
-
-    created_on: now,
-    expires_on: now + 2629800, // Session expires in a month
-
-    loc: ip_info,
-
-    valid: false,
-    challenge_code: None,
-
-    user_id: user._id
-  };
-
-  email::send(
-    ( user.username.as_str(), user.email.as_str() ), 
-    "Welcome to PhazeID",
-    &fs::read_to_string("templates/email/signup_verification.html").unwrap()
-      .replace("{{USERNAME}}", &user.username)
-      .replace("{{CODE}}", &user.email_verification_code)
-  ).await.unwrap();
-
-  app.users.insert_one(&user).await.unwrap();
-  app.sessions.insert_one(&session).await.unwrap();
-
-  // 0 - No Error
-  ws.send(Message::Text(encrypt(format!("0{}{}", token, session._id), &remote_pub_key).unwrap().into())).await.unwrap();
-  Ok(user)
+  }
 }
\ No newline at end of file
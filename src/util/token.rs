@@ -1,8 +1,10 @@
 use std::{ str::FromStr, sync::Arc };
 
-use crate::{ apphandler::AppHandler, structs::{ session::Session, user::User } };
-use anyhow::anyhow;
+use crate::{ apphandler::AppHandler, structs::{ oauthapp::OAuthApplication, oautherror::OAuthError, oauthsession::OAuthSession, session::Session, user::User }, util::{ jwt, oidc } };
+use anyhow::{ anyhow, bail };
 use argon2::{ password_hash::Encoding, Argon2, PasswordHash, PasswordVerifier };
+use axum::http::HeaderMap;
+use base64::prelude::*;
 use bson::{ doc, oid::ObjectId };
 use chrono::Utc;
 use serde_json::{ json, Value };
@@ -10,23 +12,111 @@ use serde_json::{ json, Value };
 pub fn verified( user: &User, session: &Session ) -> anyhow::Result<(), Value> {
   if !user.email_verified { return Err(json!({  "procedure": "VERIFY_EMAIL", "endpoint": "/verify-email" })) }
   if !session.valid {
-    if user.has_mfa { return Err(json!({  "procedure": "VERIFY_MFA", "endpoint": "/verify-mfa" })) }
+    if user.has_mfa { return Err(json!({  "procedure": "VERIFY_MFA", "endpoint": "/verify-mfa", "passkey": !user.passkeys.is_empty() })) }
     else            { return Err(json!({  "procedure": "VERIFY", "endpoint": "/verify" })) }
   }
 
   Ok(())
 }
 
-pub async fn identify_oauth( auth: String, scope: String, app: Arc<AppHandler> ) -> anyhow::Result<User> {
-  if !auth.starts_with("Bearer "){ return Err(anyhow!("Invalid Token")) }
+pub const ELEVATION_LIFETIME: i64 = 300;
+
+// Sensitive actions need a factor re-proven in the last few minutes, not just a valid session
+pub fn elevated( user: &User, session: &Session ) -> anyhow::Result<(), Value> {
+  if session.elevated_until > Utc::now().timestamp() { return Ok(()) }
+
+  let mut methods = vec![ "password" ];
+  if user.has_mfa { methods.push("totp"); methods.push("backup_code"); }
+  if !user.passkeys.is_empty() { methods.push("passkey"); }
+
+  // Accounts made through a provider only have a random password, logging in there again works instead
+  if !user.linked_identities.is_empty() { methods.push("upstream"); }
+  let providers: Vec<&String> = user.linked_identities.iter().map(| x | &x.provider).collect();
+
+  Err(json!({  "procedure": "REAUTHENTICATE", "endpoint": "/reauthenticate", "methods": methods, "providers": providers }))
+}
+
+pub async fn identify_app( auth: String, client_id: &str, app: Arc<AppHandler> ) -> anyhow::Result<OAuthApplication> {
+  if !auth.starts_with("Bearer "){ return Err(anyhow!("Invalid App Key")) }
+
+  let client_id = ObjectId::parse_str(client_id);
+  if client_id.is_err(){ return Err(anyhow!("Invalid App")) }
+
+  let oauth_app = app.oauth_apps.find_one(doc! { "_id": client_id.unwrap() }).await.unwrap();
+  if oauth_app.is_none(){ return Err(anyhow!("Invalid App")) }
+
+  let oauth_app = oauth_app.unwrap();
+
+  let argon2 = Argon2::default();
+  let auth = auth.split_at(7).1;
+
+  let valid = argon2.verify_password(auth.as_bytes(), &PasswordHash::parse(&oauth_app.key, Encoding::B64).unwrap()).is_ok();
+  if !valid { return Err(anyhow!("Invalid App Key")) }
+
+  Ok(oauth_app)
+}
+
+// Works out which app is calling and its key, from HTTP Basic (client_secret_basic),
+// the request body (client_secret_post) or the old "Authorization: Bearer <key>" header
+pub fn client_auth( headers: &HeaderMap, client_id: Option<String>, client_secret: Option<String> ) -> Result<( String, Option<String> ), OAuthError>{
+  let auth = headers.get("Authorization").map(| x | x.to_str().unwrap_or_default().to_owned());
+
+  let Some(auth) = auth else {
+    let Some(client_id) = client_id else { return Err(OAuthError::invalid_client("Missing client_id", headers)) };
+    return Ok(( client_id, client_secret ))
+  };
+
+  if client_secret.is_some(){ return Err(OAuthError::invalid_request("Only one client authentication method can be used", headers)) }
+
+  if let Some(key) = auth.strip_prefix("Bearer "){
+    let Some(client_id) = client_id else { return Err(OAuthError::invalid_client("Missing client_id", headers)) };
+    return Ok(( client_id, Some(key.to_owned()) ))
+  }
+
+  let Some(basic) = auth.strip_prefix("Basic ") else { return Err(OAuthError::invalid_client("Unsupported client authentication method", headers)) };
+
+  let Some(basic) = BASE64_STANDARD.decode(basic).ok().and_then(| x | String::from_utf8(x).ok()) else {
+    return Err(OAuthError::invalid_client("Invalid client credentials", headers)) };
+
+  let Some(( id, secret )) = basic.split_once(':') else { return Err(OAuthError::invalid_client("Invalid client credentials", headers)) };
+
+  // Both halves are form encoded before being joined (RFC 6749 2.3.1)
+  let ( Ok(id), Ok(secret) ) = ( urlencoding::decode(id), urlencoding::decode(secret) ) else {
+    return Err(OAuthError::invalid_client("Invalid client credentials", headers)) };
+
+  if client_id.is_some_and(| x | x != id){ return Err(OAuthError::invalid_request("client_id does not match the Authorization header", headers)) }
+
+  Ok(( id.into_owned(), Some(secret.into_owned()) ))
+}
+
+// For endpoints only confidential clients can use (introspection, revocation), so the app key is required
+pub async fn identify_client( headers: &HeaderMap, client_id: Option<String>, client_secret: Option<String>, app: &AppHandler ) -> Result<OAuthApplication, OAuthError>{
+  let ( client_id, client_secret ) = client_auth(headers, client_id, client_secret)?;
+
+  let Some(client_secret) = client_secret else { return Err(OAuthError::invalid_client("Invalid App Key", headers)) };
+  let Ok(client_id) = ObjectId::parse_str(&client_id) else { return Err(OAuthError::invalid_client("Invalid App", headers)) };
+
+  let Some(oauth_app) = app.oauth_apps.find_one(doc! { "_id": client_id }).await.unwrap() else {
+    return Err(OAuthError::invalid_client("Invalid App", headers)) };
+
+  let valid = Argon2::default().verify_password(client_secret.as_bytes(), &PasswordHash::parse(&oauth_app.key, Encoding::B64).unwrap()).is_ok();
+  if !valid { return Err(OAuthError::invalid_client("Invalid App Key", headers)) }
+
+  Ok(oauth_app)
+}
+
+pub async fn identify_oauth_session( token: &str, app: Arc<AppHandler> ) -> anyhow::Result<OAuthSession> {
+  if token.len() < 25 || !token.is_char_boundary(24) { return Err(anyhow!("Invalid Token")) }
 
   let argon2 = Argon2::default();
   let now = Utc::now().timestamp();
 
-  let auth = auth.split_at(7).1;
-  let ( token_id, token ) = auth.split_at(24);
+  let ( token_id, token ) = token.split_at(24);
+  let token_id = ObjectId::parse_str(token_id);
 
-  let oauth_session = app.oauth_sessions.find_one(doc! { "_id": ObjectId::parse_str(token_id).unwrap() }).await.unwrap();
+  if token_id.is_err(){ return Err(anyhow!("Invalid Token")) }
+
+  let oauth_session = app.oauth_sessions.find_one(doc! { "_id": token_id.unwrap() }).await.unwrap();
   if oauth_session.is_none(){ return Err(anyhow!("Invalid Token")) }
 
   let oauth_session = oauth_session.unwrap();
@@ -39,7 +129,58 @@ pub async fn identify_oauth( auth: String, scope: String, app: Arc<AppHandler> )
   let valid = argon2.verify_password(token.as_bytes(), &PasswordHash::parse(&oauth_session.token, Encoding::B64).unwrap()).is_ok();
   if !valid { return Err(anyhow!("Invalid Token")) }
 
-  if !oauth_session.scopes.contains(&scope){ return Err(anyhow!("Invalid Token")) }
+  Ok(oauth_session)
+}
+
+pub fn is_jwt( token: &str ) -> bool{
+  token.split('.').count() == 3
+}
+
+// JWT access tokens are checked without touching the database, the session is built from the claims
+pub async fn identify_jwt( token: &str, app: &AppHandler ) -> anyhow::Result<OAuthSession> {
+  let ( header, claims ) = jwt::verify(app, token).await?;
+  if header["typ"] != "at+jwt" || claims["iss"] != oidc::ISSUER { bail!("Invalid Token") }
+
+  let now = Utc::now().timestamp();
+
+  let exp = claims["exp"].as_i64().unwrap_or_default();
+  if exp < now { bail!("Invalid Token") }
+
+  let ( Some(jti), Some(aud), Some(sub) ) = ( claims["jti"].as_str(), claims["aud"].as_str(), claims["sub"].as_str() ) else { bail!("Invalid Token") };
+
+  Ok(OAuthSession {
+    _id: ObjectId::parse_str(jti)?,
+    token: "".into(),
+
+    created_on: claims["iat"].as_i64().unwrap_or_default(),
+    expires_on: exp,
+
+    app_id: ObjectId::parse_str(aud)?,
+    app_name: "".into(),
+
+    user_id: Some(ObjectId::parse_str(sub)?),
+    scopes: claims["scope"].as_str().unwrap_or_default().split(' ').filter(| x | !x.is_empty()).map(| x | x.to_owned()).collect(),
+
+    grant_id: None
+  })
+}
+
+pub async fn identify_oauth_token( token: &str, app: Arc<AppHandler> ) -> anyhow::Result<OAuthSession> {
+  if is_jwt(token) { identify_jwt(token, &app).await } else { identify_oauth_session(token, app).await }
+}
+
+// Any one of `scopes` is enough
+pub async fn identify_oauth( auth: String, scopes: &[&str], app: Arc<AppHandler> ) -> anyhow::Result<( User, OAuthSession )> {
+  if !auth.starts_with("Bearer "){ return Err(anyhow!("Invalid Token")) }
+
+  let oauth_session = identify_oauth_token(auth.split_at(7).1, app.clone()).await?;
+  if !oauth_session.scopes.iter().any(| x | scopes.contains(&x.as_str())){ return Err(anyhow!("Invalid Token")) }
+
+  if oauth_session.user_id.is_none(){ return Err(anyhow!("Invalid Token")) }
+
+  // Suspending an app removes its sessions, but JWT access tokens live on until they expire
+  let suspended = app.oauth_apps.count_documents(doc! { "_id": oauth_session.app_id, "suspended": true }).await.unwrap();
+  if suspended > 0 { return Err(anyhow!("Invalid Token")) }
 
   let user = app.users.find_one(doc! { "_id": oauth_session.user_id }).await.unwrap();
   if user.is_none(){
@@ -47,7 +188,35 @@ pub async fn identify_oauth( auth: String, scope: String, app: Arc<AppHandler> )
     return Err(anyhow!("Invalid Token"))
   }
 
-  Ok(user.unwrap())
+  Ok(( user.unwrap(), oauth_session ))
+}
+
+pub async fn identify_oauth_app( auth: String, scope: String, app: Arc<AppHandler> ) -> anyhow::Result<OAuthSession> {
+  if !auth.starts_with("Bearer "){ return Err(anyhow!("Invalid Token")) }
+
+  let oauth_session = identify_oauth_session(auth.split_at(7).1, app).await?;
+  if oauth_session.user_id.is_some(){ return Err(anyhow!("Invalid Token")) }
+
+  if !oauth_session.scopes.contains(&scope){ return Err(anyhow!("Invalid Token")) }
+
+  Ok(oauth_session)
+}
+
+// For the developer dashboard, developers can only touch their own apps
+pub async fn identify_owned_app( id: &str, user: &User, app: &AppHandler ) -> anyhow::Result<OAuthApplication> {
+  let Ok(app_id) = ObjectId::parse_str(id) else { bail!("App not found") };
+
+  let oauth_app = app.oauth_apps.find_one(doc! { "_id": app_id, "owner_id": user._id }).await.unwrap();
+  oauth_app.ok_or(anyhow!("App not found"))
+}
+
+// Either the app key with a client_id, or an app only token with the "deletion_queue" scope
+pub async fn identify_deletion_queue( auth: String, client_id: Option<&str>, app: Arc<AppHandler> ) -> anyhow::Result<ObjectId> {
+  if let Some(client_id) = client_id {
+    Ok(identify_app(auth, client_id, app).await?._id)
+  } else{
+    Ok(identify_oauth_app(auth, "deletion_queue".into(), app).await?.app_id)
+  }
 }
 
 pub async fn identify( token: String, app: Arc<AppHandler>, ip: String ) -> anyhow::Result<( User, Session )> {
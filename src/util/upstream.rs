@@ -0,0 +1,375 @@
+use std::{ env, fs };
+
+use anyhow::bail;
+use argon2::{ password_hash::SaltString, Argon2, PasswordHasher };
+use bson::{ doc, oid::ObjectId };
+use chrono::Utc;
+use rand::{ distributions::Alphanumeric, rngs::OsRng, Rng };
+use serde_json::Value;
+use url::Url;
+
+use crate::{ apphandler::AppHandler, structs::{ upstream::{ LinkedIdentity, UpstreamProvider, UpstreamState }, user::User }, util::{ pkce, signup::{ self, SignupError } } };
+
+pub const STATE_LIFETIME: i64 = 600;
+
+// What we got back about the user from the upstream provider
+pub struct UpstreamUser{
+  pub subject: String,
+  pub username: Option<String>,
+  pub email: Option<String>,
+  pub email_verified: bool
+}
+
+impl UpstreamUser{
+  pub fn identity( &self, provider: &UpstreamProvider ) -> LinkedIdentity{
+    LinkedIdentity {
+      provider: provider.id.clone(),
+      subject: self.subject.clone(),
+
+      username: self.username.clone(),
+      email: self.email.clone(),
+
+      linked_on: Utc::now().timestamp()
+    }
+  }
+}
+
+// UPSTREAM_PROVIDERS points at a JSON file with a list of providers, leaving it unset turns the feature off
+pub async fn load() -> anyhow::Result<Vec<UpstreamProvider>>{
+  let Ok(path) = env::var("UPSTREAM_PROVIDERS") else { return Ok(vec![]) };
+  let configured: Vec<UpstreamProvider> = serde_json::from_str(&fs::read_to_string(path)?)?;
+  let mut providers = vec![];
+
+  for mut provider in configured {
+    if let Some(issuer) = &provider.issuer {
+      // One provider being down shouldn't stop us from starting, it just isn't offered until the next restart
+      let config = match discover(issuer).await {
+        Ok(config) => config,
+        Err(err) => {
+          log::error!("Skipping upstream provider {}, discovery failed: {}", provider.id, err);
+          continue
+        }
+      };
+
+      let endpoint = | key: &str | config[key].as_str().map(| x | x.to_owned());
+
+      if provider.authorization_endpoint.is_none() { provider.authorization_endpoint = endpoint("authorization_endpoint"); }
+      if provider.token_endpoint.is_none() { provider.token_endpoint = endpoint("token_endpoint"); }
+      if provider.userinfo_endpoint.is_none() { provider.userinfo_endpoint = endpoint("userinfo_endpoint"); }
+    }
+
+    if provider.authorization_endpoint.is_none() || provider.token_endpoint.is_none() || provider.userinfo_endpoint.is_none() {
+      bail!("Upstream provider {} is missing endpoints", provider.id) }
+
+    if env::var(&provider.client_secret_env).is_err() { bail!("Upstream provider {} is missing {}", provider.id, provider.client_secret_env) }
+
+    providers.push(provider);
+  }
+
+  Ok(providers)
+}
+
+async fn discover( issuer: &str ) -> anyhow::Result<Value>{
+  let res = reqwest::get(format!("{}/.well-known/openid-configuration", issuer.trim_end_matches('/'))).await?;
+  Ok(serde_json::from_str(&res.text().await?)?)
+}
+
+pub fn find<'a>( app: &'a AppHandler, id: &str ) -> Option<&'a UpstreamProvider>{
+  app.upstream_providers.iter().find(| x | x.id == id)
+}
+
+// Returns the URL to send the user to
+pub async fn start( app: &AppHandler, provider: &UpstreamProvider, mode: &str, user_id: Option<ObjectId>, session_id: Option<ObjectId> ) -> String{
+  let now = Utc::now().timestamp();
+  app.upstream_states.delete_many(doc! { "expires_on": { "$lt": now } }).await.unwrap();
+
+  let state = UpstreamState {
+    _id: ObjectId::new(),
+    state: rand::thread_rng().sample_iter(&Alphanumeric).take(32).map(char::from).collect(),
+    provider: provider.id.clone(),
+    mode: mode.to_owned(),
+
+    user_id,
+    session_id,
+    code_verifier: if provider.pkce { Some(rand::thread_rng().sample_iter(&Alphanumeric).take(64).map(char::from).collect()) } else { None },
+
+    expires_on: now + STATE_LIFETIME
+  };
+
+  app.upstream_states.insert_one(&state).await.unwrap();
+
+  let mut url = Url::parse(provider.authorization_endpoint.as_ref().unwrap()).unwrap();
+  url.query_pairs_mut()
+    .append_pair("response_type", "code")
+    .append_pair("client_id", &provider.client_id)
+    .append_pair("redirect_uri", &provider.redirect_uri)
+    .append_pair("scope", &provider.scope)
+    .append_pair("state", &state.state);
+
+  // Re-authenticating should mean actually logging in again, not a silent bounce off an existing upstream session
+  if mode == "reauthenticate" {
+    url.query_pairs_mut().append_pair("prompt", "login").append_pair("max_age", "0");
+  }
+
+  if let Some(code_verifier) = &state.code_verifier {
+    url.query_pairs_mut()
+      .append_pair("code_challenge", &pkce::challenge(code_verifier))
+      .append_pair("code_challenge_method", "S256");
+  }
+
+  url.to_string()
+}
+
+// States are single use, a replayed callback finds nothing
+pub async fn take_state( app: &AppHandler, state: &str ) -> Option<UpstreamState>{
+  app.upstream_states.find_one_and_delete(doc! {
+    "state": state,
+    "expires_on": { "$gt": Utc::now().timestamp() }
+  }).await.unwrap()
+}
+
+pub async fn exchange( provider: &UpstreamProvider, code: &str, code_verifier: Option<String> ) -> anyhow::Result<UpstreamUser>{
+  let client_secret = env::var(&provider.client_secret_env)?;
+
+  let mut params = vec![
+    ( "grant_type", "authorization_code".to_owned() ),
+    ( "code", code.to_owned() ),
+    ( "redirect_uri", provider.redirect_uri.clone() ),
+    ( "client_id", provider.client_id.clone() ),
+    ( "client_secret", client_secret )
+  ];
+
+  if let Some(code_verifier) = code_verifier { params.push(( "code_verifier", code_verifier )); }
+
+  let client = reqwest::Client::new();
+  let res = client.post(provider.token_endpoint.as_ref().unwrap())
+    .header("Accept", "application/json")
+    .form(&params)
+    .send().await?;
+
+  if !res.status().is_success() { bail!("Upstream token endpoint returned {}", res.status()) }
+
+  let token: Value = serde_json::from_str(&res.text().await?)?;
+  let Some(access_token) = token["access_token"].as_str() else { bail!("Upstream didn't return an access token") };
+
+  let res = client.get(provider.userinfo_endpoint.as_ref().unwrap())
+    .bearer_auth(access_token)
+    .header("Accept", "application/json")
+    .header("User-Agent", "PhazeID")
+    .send().await?;
+
+  if !res.status().is_success() { bail!("Upstream userinfo endpoint returned {}", res.status()) }
+
+  let userinfo: Value = serde_json::from_str(&res.text().await?)?;
+  map_userinfo(provider, &userinfo)
+}
+
+// Pulls the fields the provider is configured for out of its userinfo response
+fn map_userinfo( provider: &UpstreamProvider, userinfo: &Value ) -> anyhow::Result<UpstreamUser>{
+  let Some(subject) = claim(userinfo, &provider.subject_field) else { bail!("Upstream didn't return a subject") };
+
+  let email_verified = provider.trust_email ||
+    provider.email_verified_field.as_ref().is_some_and(| x | userinfo[x].as_bool() == Some(true));
+
+  Ok(UpstreamUser {
+    subject,
+    username: claim(userinfo, &provider.username_field),
+    email: claim(userinfo, &provider.email_field),
+    email_verified
+  })
+}
+
+// Some providers (GitHub) use numbers for IDs
+fn claim( userinfo: &Value, field: &str ) -> Option<String>{
+  match &userinfo[field] {
+    Value::String(x) if !x.is_empty() => Some(x.clone()),
+    Value::Number(x) => Some(x.to_string()),
+    _ => None
+  }
+}
+
+pub async fn find_user( app: &AppHandler, provider: &str, subject: &str ) -> Option<User>{
+  app.users.find_one(doc! {
+    "linked_identities": { "$elemMatch": { "provider": provider, "subject": subject } }
+  }).await.unwrap()
+}
+
+// Username limits are in bytes, so cut on a character boundary rather than splitting one
+fn truncate( name: &str, max: usize ) -> &str{
+  let mut end = max.min(name.len());
+  while !name.is_char_boundary(end) { end -= 1; }
+
+  &name[..end]
+}
+
+// First login through a provider, goes through the same checks as a normal signup
+pub async fn signup( app: &AppHandler, provider: &UpstreamProvider, upstream_user: &UpstreamUser ) -> Result<User, SignupError>{
+  // Without a verified email we'd have no way to recover the account
+  let Some(email) = upstream_user.email.clone().filter(| _ | upstream_user.email_verified) else { return Err(SignupError::InvalidEmail) };
+
+  let base = upstream_user.username.clone()
+    .unwrap_or_else(|| email.split('@').next().unwrap_or_default().to_owned());
+
+  let base: String = base.chars().filter(| x | !x.is_whitespace()).collect();
+  let base = truncate(&base, 40).to_owned();
+
+  // Fall back to a numbered name if theirs is taken here
+  let mut username = base.clone();
+  let mut attempts = 0;
+
+  loop {
+    match signup::check(&username, &email, app).await {
+      Ok(()) => break,
+      Err(SignupError::UsernameInUse) if attempts < 5 => {
+        attempts += 1;
+        username = format!("{}{}", base, rand::thread_rng().gen_range(1000..10000));
+      },
+      Err(err) => return Err(err)
+    }
+  }
+
+  // They log in through the provider, but can set a password later through a reset
+  let password: String = rand::thread_rng().sample_iter(&Alphanumeric).take(64).map(char::from).collect();
+  let salt = SaltString::generate(&mut OsRng);
+  let password_hash = Argon2::default().hash_password(password.as_bytes(), &salt).unwrap().to_string();
+
+  let mut user = signup::new_user(username, password_hash, email);
+
+  user.email_verified = true;
+  user.linked_identities.push(upstream_user.identity(provider));
+
+  app.users.insert_one(&user).await.unwrap();
+  Ok(user)
+}
+
+#[cfg(test)]
+mod tests {
+  use serde_json::json;
+  use tokio::{ io::{ AsyncReadExt, AsyncWriteExt }, net::TcpListener };
+
+  use super::*;
+
+  fn configured( config: Value ) -> UpstreamProvider{
+    let mut base = json!({
+      "id": "test",
+      "name": "Test",
+      "client_id": "client",
+      "client_secret_env": "UPSTREAM_TEST_SECRET",
+      "redirect_uri": "https://id.phazed.xyz/upstream/callback",
+      "scope": "openid email profile"
+    });
+
+    for ( key, value ) in config.as_object().unwrap() { base[key] = value.clone(); }
+    serde_json::from_value(base).unwrap()
+  }
+
+  #[test]
+  fn claims(){
+    let userinfo = json!({ "id": 583231, "login": "octocat", "name": "", "admin": true });
+
+    assert_eq!(claim(&userinfo, "id").as_deref(), Some("583231"));
+    assert_eq!(claim(&userinfo, "login").as_deref(), Some("octocat"));
+    assert_eq!(claim(&userinfo, "name"), None);
+    assert_eq!(claim(&userinfo, "admin"), None);
+    assert_eq!(claim(&userinfo, "missing"), None);
+  }
+
+  #[test]
+  fn maps_oidc_userinfo(){
+    let provider = configured(json!({ "email_verified_field": "email_verified" }));
+
+    let user = map_userinfo(&provider, &json!({
+      "sub": "abc", "preferred_username": "jane", "email": "jane@example.com", "email_verified": true
+    })).unwrap();
+
+    assert_eq!(user.subject, "abc");
+    assert_eq!(user.username.as_deref(), Some("jane"));
+    assert_eq!(user.email.as_deref(), Some("jane@example.com"));
+    assert!(user.email_verified);
+
+    let user = map_userinfo(&provider, &json!({ "sub": "abc", "email": "jane@example.com", "email_verified": "true" })).unwrap();
+    assert!(!user.email_verified);
+    assert_eq!(user.username, None);
+
+    assert!(map_userinfo(&provider, &json!({ "preferred_username": "jane" })).is_err());
+    assert!(map_userinfo(&provider, &json!({ "sub": "" })).is_err());
+  }
+
+  #[test]
+  fn maps_custom_fields(){
+    let provider = configured(json!({ "subject_field": "id", "username_field": "login" }));
+    let userinfo = json!({ "id": 583231, "login": "octocat", "email": "octocat@github.com", "email_verified": true });
+
+    let user = map_userinfo(&provider, &userinfo).unwrap();
+
+    assert_eq!(user.subject, "583231");
+    assert_eq!(user.username.as_deref(), Some("octocat"));
+
+    // Without a field to check, the email is only trusted when configured to be
+    assert!(!user.email_verified);
+
+    let trusted = configured(json!({ "subject_field": "id", "username_field": "login", "trust_email": true }));
+    assert!(map_userinfo(&trusted, &userinfo).unwrap().email_verified);
+  }
+
+  #[test]
+  fn truncates_on_char_boundary(){
+    assert_eq!(truncate("jane", 40), "jane");
+    assert_eq!(truncate("abcdef", 3), "abc");
+    assert_eq!(truncate("aé", 2), "a");
+    assert_eq!(truncate("日本語", 7), "日本");
+    assert!(truncate(&"ü".repeat(30), 40).len() <= 40);
+  }
+
+  // Stand-in IdP that answers the token and userinfo requests exchange() makes
+  async fn mock_idp() -> String{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+      for _ in 0..2 {
+        let ( mut socket, _ ) = listener.accept().await.unwrap();
+
+        let mut buf = vec![ 0u8; 8192 ];
+        let n = socket.read(&mut buf).await.unwrap();
+        let req = String::from_utf8_lossy(&buf[..n]).to_string();
+
+        let body = if req.starts_with("POST /token") {
+          assert!(req.contains("code=upstream-code"));
+          assert!(req.contains("client_secret=shh"));
+          assert!(req.contains("code_verifier=verifier"));
+
+          json!({ "access_token": "upstream-token", "token_type": "Bearer" })
+        } else {
+          assert!(req.starts_with("GET /userinfo"));
+          assert!(req.to_lowercase().contains("authorization: bearer upstream-token"));
+
+          json!({ "sub": "abc", "preferred_username": "jane", "email": "jane@example.com", "email_verified": true })
+        }.to_string();
+
+        let res = format!("HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", body.len(), body);
+        socket.write_all(res.as_bytes()).await.unwrap();
+      }
+    });
+
+    format!("http://{}", addr)
+  }
+
+  #[tokio::test]
+  async fn exchanges_code(){
+    env::set_var("UPSTREAM_TEST_SECRET", "shh");
+    let idp = mock_idp().await;
+
+    let provider = configured(json!({
+      "token_endpoint": format!("{}/token", idp),
+      "userinfo_endpoint": format!("{}/userinfo", idp),
+      "email_verified_field": "email_verified"
+    }));
+
+    let user = exchange(&provider, "upstream-code", Some("verifier".into())).await.unwrap();
+
+    assert_eq!(user.subject, "abc");
+    assert_eq!(user.username.as_deref(), Some("jane"));
+    assert!(user.email_verified);
+  }
+}
\ No newline at end of file
@@ -0,0 +1,401 @@
+use anyhow::bail;
+use axum::http::HeaderMap;
+use base64::{ prelude::BASE64_URL_SAFE_NO_PAD, Engine };
+use bson::{ doc, oid::ObjectId };
+use chrono::Utc;
+use ciborium::Value as Cbor;
+use p256::ecdsa::{ signature::Verifier, Signature, VerifyingKey };
+use rand::RngCore;
+use serde_json::{ json, Value };
+use sha2::{ Digest, Sha256 };
+use url::Url;
+
+use crate::{ apphandler::AppHandler, structs::{ passkey::{ Passkey, PasskeyAssertion, WebAuthnChallenge }, user::User }, util::cors::ALLOWED_ORIGINS };
+
+pub const CHALLENGE_LIFETIME: i64 = 300;
+pub const MAX_PASSKEYS: usize = 10;
+
+// COSE algorithm -7, the only one we ask authenticators for
+const ES256: i64 = -7;
+
+const FLAG_USER_PRESENT: u8 = 0x01;
+const FLAG_USER_VERIFIED: u8 = 0x04;
+const FLAG_ATTESTED_CREDENTIAL: u8 = 0x40;
+
+pub struct NewCredential{
+  pub id: String,
+  pub public_key: String,
+  pub sign_count: u32
+}
+
+struct AuthenticatorData<'a>{
+  flags: u8,
+  sign_count: u32,
+  rest: &'a [u8]
+}
+
+// The relying party is whichever of our frontends the request came from, passkeys are bound to its host
+pub fn rp_id( headers: &HeaderMap ) -> Option<String>{
+  let origin = headers.get("Origin")?.to_str().ok()?;
+  if !ALLOWED_ORIGINS.contains(&origin){ return None }
+
+  Url::parse(origin).ok()?.host_str().map(| x | x.to_owned())
+}
+
+pub async fn new_challenge( app: &AppHandler, kind: &str, rp_id: String, user_id: Option<ObjectId>, session_id: Option<ObjectId> ) -> WebAuthnChallenge{
+  let now = Utc::now().timestamp();
+  app.webauthn_challenges.delete_many(doc! { "expires_on": { "$lt": now } }).await.unwrap();
+
+  let mut challenge = [ 0u8; 32 ];
+  rand::thread_rng().fill_bytes(&mut challenge);
+
+  let challenge = WebAuthnChallenge {
+    _id: ObjectId::new(),
+    kind: kind.to_owned(),
+    challenge: BASE64_URL_SAFE_NO_PAD.encode(challenge),
+    rp_id,
+
+    user_id,
+    session_id,
+
+    expires_on: now + CHALLENGE_LIFETIME
+  };
+
+  app.webauthn_challenges.insert_one(&challenge).await.unwrap();
+  challenge
+}
+
+// Challenges are single use, they're gone whether the ceremony works or not
+pub async fn take_challenge( app: &AppHandler, id: &str, kind: &str ) -> Option<WebAuthnChallenge>{
+  let id = ObjectId::parse_str(id).ok()?;
+
+  app.webauthn_challenges.find_one_and_delete(doc! {
+    "_id": id,
+    "kind": kind,
+    "expires_on": { "$gt": Utc::now().timestamp() }
+  }).await.unwrap()
+}
+
+// Options for navigator.credentials.get(), no credentials listed means any discoverable one will do
+pub fn request_options( challenge: &WebAuthnChallenge, passkeys: &[Passkey], user_verification: &str ) -> Value{
+  json!({
+    "challenge_id": challenge._id.to_hex(),
+    "publicKey": {
+      "challenge": challenge.challenge,
+      "rpId": challenge.rp_id,
+      "timeout": CHALLENGE_LIFETIME * 1000,
+      "userVerification": user_verification,
+      "allowCredentials": passkeys.iter().map(| x | json!({ "type": "public-key", "id": x.id })).collect::<Vec<_>>()
+    }
+  })
+}
+
+pub fn find_passkey<'a>( user: &'a User, id: &str ) -> Option<&'a Passkey>{
+  user.passkeys.iter().find(| x | x.id == id)
+}
+
+pub async fn update_sign_count( app: &AppHandler, user_id: ObjectId, id: &str, sign_count: u32 ){
+  app.users.update_one(doc! { "_id": user_id, "passkeys.id": id }, doc! {
+    "$set": { "passkeys.$.sign_count": sign_count as i64, "passkeys.$.last_used": Utc::now().timestamp() }
+  }).await.unwrap();
+}
+
+pub fn verify_registration( challenge: &WebAuthnChallenge, client_data_json: &str, attestation_object: &str ) -> anyhow::Result<NewCredential>{
+  let client_data_json = BASE64_URL_SAFE_NO_PAD.decode(client_data_json)?;
+  let attestation_object = BASE64_URL_SAFE_NO_PAD.decode(attestation_object)?;
+
+  check_client_data(&client_data_json, "webauthn.create", challenge)?;
+
+  let Cbor::Map(attestation) = ciborium::de::from_reader::<Cbor, _>(attestation_object.as_slice())? else { bail!("Invalid attestation object") };
+
+  // We don't check who made the authenticator, so only take credentials that don't claim to be attested
+  if cbor_get(&attestation, Cbor::Text("fmt".into())) != Some(&Cbor::Text("none".into())) { bail!("Unsupported attestation format") }
+
+  let Some(Cbor::Bytes(auth_data)) = cbor_get(&attestation, Cbor::Text("authData".into())) else { bail!("Invalid attestation object") };
+  let auth_data = parse_authenticator_data(auth_data, &challenge.rp_id, false)?;
+
+  if auth_data.flags & FLAG_ATTESTED_CREDENTIAL == 0 || auth_data.rest.len() < 18 { bail!("Missing credential") }
+
+  // 16 byte AAGUID, 2 byte credential ID length, the credential ID, then the COSE public key
+  let id_length = u16::from_be_bytes([ auth_data.rest[16], auth_data.rest[17] ]) as usize;
+  if auth_data.rest.len() < 18 + id_length { bail!("Missing credential") }
+
+  let id = &auth_data.rest[18..18 + id_length];
+
+  let mut key = &auth_data.rest[18 + id_length..];
+  let key: Cbor = ciborium::de::from_reader(&mut key)?;
+
+  Ok(NewCredential {
+    id: BASE64_URL_SAFE_NO_PAD.encode(id),
+    public_key: BASE64_URL_SAFE_NO_PAD.encode(cose_to_sec1(&key)?),
+    sign_count: auth_data.sign_count
+  })
+}
+
+// Returns the new signature counter, which the caller needs to store
+pub fn verify_assertion( challenge: &WebAuthnChallenge, passkey: &Passkey, assertion: &PasskeyAssertion, require_user_verification: bool ) -> anyhow::Result<u32>{
+  let client_data_json = BASE64_URL_SAFE_NO_PAD.decode(&assertion.client_data_json)?;
+  let authenticator_data = BASE64_URL_SAFE_NO_PAD.decode(&assertion.authenticator_data)?;
+  let signature = BASE64_URL_SAFE_NO_PAD.decode(&assertion.signature)?;
+
+  check_client_data(&client_data_json, "webauthn.get", challenge)?;
+  let auth_data = parse_authenticator_data(&authenticator_data, &challenge.rp_id, require_user_verification)?;
+
+  let key = VerifyingKey::from_sec1_bytes(&BASE64_URL_SAFE_NO_PAD.decode(&passkey.public_key)?)?;
+  let signature = Signature::from_der(&signature)?;
+
+  let mut signed = authenticator_data.clone();
+  signed.extend_from_slice(&Sha256::digest(&client_data_json));
+
+  key.verify(&signed, &signature)?;
+
+  // Authenticators that count signatures should only ever count up, going backwards means the credential was cloned
+  if ( auth_data.sign_count != 0 || passkey.sign_count != 0 ) && auth_data.sign_count <= passkey.sign_count {
+    bail!("Signature counter went backwards") }
+
+  Ok(auth_data.sign_count)
+}
+
+fn check_client_data( client_data_json: &[u8], kind: &str, challenge: &WebAuthnChallenge ) -> anyhow::Result<()>{
+  let client_data: Value = serde_json::from_slice(client_data_json)?;
+
+  if client_data["type"] != kind { bail!("Invalid client data type") }
+  if client_data["challenge"] != challenge.challenge.as_str() { bail!("Invalid challenge") }
+
+  let origin = client_data["origin"].as_str().unwrap_or_default();
+  if !ALLOWED_ORIGINS.contains(&origin){ bail!("Invalid origin") }
+
+  if Url::parse(origin)?.host_str() != Some(challenge.rp_id.as_str()) { bail!("Invalid origin") }
+
+  Ok(())
+}
+
+fn parse_authenticator_data<'a>( data: &'a [u8], rp_id: &str, require_user_verification: bool ) -> anyhow::Result<AuthenticatorData<'a>>{
+  if data.len() < 37 { bail!("Invalid authenticator data") }
+  if data[..32] != Sha256::digest(rp_id.as_bytes())[..] { bail!("Invalid relying party") }
+
+  let flags = data[32];
+  if flags & FLAG_USER_PRESENT == 0 { bail!("User not present") }
+  if require_user_verification && flags & FLAG_USER_VERIFIED == 0 { bail!("User not verified") }
+
+  Ok(AuthenticatorData {
+    flags,
+    sign_count: u32::from_be_bytes([ data[33], data[34], data[35], data[36] ]),
+    rest: &data[37..]
+  })
+}
+
+fn cbor_get( map: &[( Cbor, Cbor )], key: Cbor ) -> Option<&Cbor>{
+  map.iter().find(| ( k, _ ) | *k == key).map(| ( _, v ) | v)
+}
+
+// Only EC2 keys on P-256 are accepted, anything else isn't ES256
+fn cose_to_sec1( key: &Cbor ) -> anyhow::Result<Vec<u8>>{
+  let Cbor::Map(key) = key else { bail!("Invalid public key") };
+
+  if cbor_get(key, Cbor::Integer(1.into())) != Some(&Cbor::Integer(2.into()))
+    || cbor_get(key, Cbor::Integer(3.into())) != Some(&Cbor::Integer(ES256.into()))
+    || cbor_get(key, Cbor::Integer((-1).into())) != Some(&Cbor::Integer(1.into()))
+  { bail!("Unsupported public key type") }
+
+  let ( Some(Cbor::Bytes(x)), Some(Cbor::Bytes(y)) ) = ( cbor_get(key, Cbor::Integer((-2).into())), cbor_get(key, Cbor::Integer((-3).into())) ) else {
+    bail!("Invalid public key") };
+
+  if x.len() != 32 || y.len() != 32 { bail!("Invalid public key") }
+
+  let mut sec1 = vec![ 0x04 ];
+  sec1.extend_from_slice(x);
+  sec1.extend_from_slice(y);
+
+  // Make sure it's actually a point on the curve before we store it
+  VerifyingKey::from_sec1_bytes(&sec1)?;
+
+  Ok(sec1)
+}
+
+#[cfg(test)]
+mod tests {
+  use p256::ecdsa::{ signature::Signer, SigningKey };
+
+  use super::*;
+
+  const RP_ID: &str = "id.phazed.xyz";
+  const ORIGIN: &str = "https://id.phazed.xyz";
+
+  fn b64( data: &[u8] ) -> String{
+    BASE64_URL_SAFE_NO_PAD.encode(data)
+  }
+
+  fn challenge() -> WebAuthnChallenge{
+    WebAuthnChallenge {
+      _id: ObjectId::new(),
+      kind: "register".into(),
+      challenge: "abc123".into(),
+      rp_id: RP_ID.into(),
+
+      user_id: None,
+      session_id: None,
+
+      expires_on: 0
+    }
+  }
+
+  fn client_data( kind: &str, origin: &str ) -> Vec<u8>{
+    json!({ "type": kind, "challenge": "abc123", "origin": origin }).to_string().into_bytes()
+  }
+
+  fn authenticator_data( rp_id: &str, flags: u8, sign_count: u32 ) -> Vec<u8>{
+    let mut data = Sha256::digest(rp_id.as_bytes()).to_vec();
+    data.push(flags);
+    data.extend_from_slice(&sign_count.to_be_bytes());
+
+    data
+  }
+
+  fn cose_key( key: &SigningKey, alg: i64 ) -> Vec<u8>{
+    let point = key.verifying_key().to_encoded_point(false);
+
+    let cose = Cbor::Map(vec![
+      ( Cbor::Integer(1.into()), Cbor::Integer(2.into()) ),
+      ( Cbor::Integer(3.into()), Cbor::Integer(alg.into()) ),
+      ( Cbor::Integer((-1).into()), Cbor::Integer(1.into()) ),
+      ( Cbor::Integer((-2).into()), Cbor::Bytes(point.x().unwrap().to_vec()) ),
+      ( Cbor::Integer((-3).into()), Cbor::Bytes(point.y().unwrap().to_vec()) )
+    ]);
+
+    let mut out = vec![];
+    ciborium::ser::into_writer(&cose, &mut out).unwrap();
+
+    out
+  }
+
+  // What a software authenticator hands back from navigator.credentials.create()
+  fn attestation( fmt: &str, key: &SigningKey, alg: i64, credential_id: &[u8] ) -> Vec<u8>{
+    let mut auth_data = authenticator_data(RP_ID, FLAG_USER_PRESENT | FLAG_USER_VERIFIED | FLAG_ATTESTED_CREDENTIAL, 1);
+    auth_data.extend_from_slice(&[ 0u8; 16 ]);
+    auth_data.extend_from_slice(&( credential_id.len() as u16 ).to_be_bytes());
+    auth_data.extend_from_slice(credential_id);
+    auth_data.extend_from_slice(&cose_key(key, alg));
+
+    let attestation = Cbor::Map(vec![
+      ( Cbor::Text("fmt".into()), Cbor::Text(fmt.into()) ),
+      ( Cbor::Text("attStmt".into()), Cbor::Map(vec![]) ),
+      ( Cbor::Text("authData".into()), Cbor::Bytes(auth_data) )
+    ]);
+
+    let mut out = vec![];
+    ciborium::ser::into_writer(&attestation, &mut out).unwrap();
+
+    out
+  }
+
+  fn passkey( key: &SigningKey, sign_count: u32 ) -> Passkey{
+    Passkey {
+      id: b64(&[ 7u8; 16 ]),
+      name: "Test".into(),
+
+      public_key: b64(key.verifying_key().to_encoded_point(false).as_bytes()),
+      sign_count,
+
+      created_on: 0,
+      last_used: 0
+    }
+  }
+
+  // And from navigator.credentials.get()
+  fn assertion( key: &SigningKey, auth_data: Vec<u8>, client_data: Vec<u8> ) -> PasskeyAssertion{
+    let mut signed = auth_data.clone();
+    signed.extend_from_slice(&Sha256::digest(&client_data));
+
+    let signature: p256::ecdsa::Signature = key.sign(&signed);
+
+    PasskeyAssertion {
+      challenge_id: ObjectId::new().to_hex(),
+      credential_id: b64(&[ 7u8; 16 ]),
+      client_data_json: b64(&client_data),
+      authenticator_data: b64(&auth_data),
+      signature: b64(signature.to_der().as_bytes()),
+      user_handle: None
+    }
+  }
+
+  #[test]
+  fn registers_none_attestation(){
+    let key = SigningKey::random(&mut rand::thread_rng());
+    let attestation = attestation("none", &key, ES256, &[ 7u8; 16 ]);
+
+    let credential = verify_registration(&challenge(), &b64(&client_data("webauthn.create", ORIGIN)), &b64(&attestation)).unwrap();
+
+    assert_eq!(credential.id, b64(&[ 7u8; 16 ]));
+    assert_eq!(credential.public_key, passkey(&key, 0).public_key);
+    assert_eq!(credential.sign_count, 1);
+  }
+
+  #[test]
+  fn registration_checks(){
+    let key = SigningKey::random(&mut rand::thread_rng());
+    let good = b64(&attestation("none", &key, ES256, &[ 7u8; 16 ]));
+    let client = b64(&client_data("webauthn.create", ORIGIN));
+
+    assert!(verify_registration(&challenge(), &b64(&client_data("webauthn.get", ORIGIN)), &good).is_err());
+    assert!(verify_registration(&challenge(), &b64(&client_data("webauthn.create", "https://evil.com")), &good).is_err());
+    assert!(verify_registration(&challenge(), &client, &b64(&attestation("packed", &key, ES256, &[ 7u8; 16 ]))).is_err());
+    assert!(verify_registration(&challenge(), &client, &b64(&attestation("none", &key, -257, &[ 7u8; 16 ]))).is_err());
+
+    // Challenge for a different relying party
+    let mut other = challenge();
+    other.rp_id = "localhost".into();
+    assert!(verify_registration(&other, &client, &good).is_err());
+  }
+
+  #[test]
+  fn verifies_assertions(){
+    let key = SigningKey::random(&mut rand::thread_rng());
+    let client = client_data("webauthn.get", ORIGIN);
+
+    let res = verify_assertion(&challenge(), &passkey(&key, 1), &assertion(&key, authenticator_data(RP_ID, FLAG_USER_PRESENT | FLAG_USER_VERIFIED, 2), client), true);
+    assert_eq!(res.unwrap(), 2);
+  }
+
+  #[test]
+  fn assertion_flags(){
+    let key = SigningKey::random(&mut rand::thread_rng());
+    let client = client_data("webauthn.get", ORIGIN);
+
+    let present = assertion(&key, authenticator_data(RP_ID, FLAG_USER_PRESENT, 2), client.clone());
+    assert!(verify_assertion(&challenge(), &passkey(&key, 1), &present, true).is_err());
+    assert!(verify_assertion(&challenge(), &passkey(&key, 1), &present, false).is_ok());
+
+    let absent = assertion(&key, authenticator_data(RP_ID, FLAG_USER_VERIFIED, 2), client);
+    assert!(verify_assertion(&challenge(), &passkey(&key, 1), &absent, false).is_err());
+  }
+
+  #[test]
+  fn assertion_checks(){
+    let key = SigningKey::random(&mut rand::thread_rng());
+    let flags = FLAG_USER_PRESENT | FLAG_USER_VERIFIED;
+    let client = client_data("webauthn.get", ORIGIN);
+
+    // Counter going backwards means a cloned credential
+    let replayed = assertion(&key, authenticator_data(RP_ID, flags, 5), client.clone());
+    assert!(verify_assertion(&challenge(), &passkey(&key, 5), &replayed, true).is_err());
+
+    // Authenticators that don't count always send 0
+    let uncounted = assertion(&key, authenticator_data(RP_ID, flags, 0), client.clone());
+    assert_eq!(verify_assertion(&challenge(), &passkey(&key, 0), &uncounted, true).unwrap(), 0);
+
+    let other_rp = assertion(&key, authenticator_data("evil.com", flags, 2), client.clone());
+    assert!(verify_assertion(&challenge(), &passkey(&key, 1), &other_rp, true).is_err());
+
+    let other_key = SigningKey::random(&mut rand::thread_rng());
+    let forged = assertion(&other_key, authenticator_data(RP_ID, flags, 2), client.clone());
+    assert!(verify_assertion(&challenge(), &passkey(&key, 1), &forged, true).is_err());
+
+    let mut tampered = assertion(&key, authenticator_data(RP_ID, flags, 2), client);
+    tampered.authenticator_data = b64(&authenticator_data(RP_ID, flags, 3));
+    assert!(verify_assertion(&challenge(), &passkey(&key, 1), &tampered, true).is_err());
+
+    let created = assertion(&key, authenticator_data(RP_ID, flags, 2), client_data("webauthn.create", ORIGIN));
+    assert!(verify_assertion(&challenge(), &passkey(&key, 1), &created, true).is_err());
+  }
+}
\ No newline at end of file
@@ -0,0 +1,340 @@
+use std::{ env, net::{ IpAddr, SocketAddr }, sync::Arc, time::Duration };
+
+use anyhow::bail;
+use bson::{ doc, oid::ObjectId };
+use chrono::Utc;
+use hmac::{ Hmac, Mac };
+use rand::{ distributions::Alphanumeric, Rng };
+use serde_json::{ json, Value };
+use sha2::Sha256;
+use url::Url;
+
+use crate::{ apphandler::AppHandler, structs::{ user::User, webhookdelivery::WebhookDelivery } };
+
+pub const MAX_ATTEMPTS: u32 = 10;
+
+pub fn generate_secret() -> String{
+  let secret: String = rand::thread_rng().sample_iter(&Alphanumeric).take(48).map(char::from).collect();
+  format!("whsec_{}", secret)
+}
+
+// Apps verify with HMAC-SHA256( secret, "{timestamp}.{body}" ), timestamp is there to stop replays
+pub fn sign( secret: &str, timestamp: i64, payload: &str ) -> String{
+  let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+  mac.update(format!("{}.{}", timestamp, payload).as_bytes());
+
+  mac.finalize().into_bytes().iter().map(| x | format!("{:02x}", x)).collect()
+}
+
+// Plain HTTP to this machine is only for testing, and only when WEBHOOK_ALLOW_LOCALHOST is set
+fn allow_localhost( url: &Url ) -> bool{
+  env::var("WEBHOOK_ALLOW_LOCALHOST").is_ok_and(| x | x == "true") &&
+    matches!(url.host_str(), Some("localhost") | Some("127.0.0.1"))
+}
+
+pub fn check_url( url: &str ) -> anyhow::Result<Url>{
+  let url = Url::parse(url)?;
+
+  if url.scheme() != "https" && !( url.scheme() == "http" && allow_localhost(&url) ) { bail!("Webhook URL must use HTTPS") }
+  if url.host_str().is_none() { bail!("Webhook URL must have a host") }
+
+  Ok(url)
+}
+
+// Anything that isn't reachable from the internet could be one of our own services
+pub fn is_public( ip: IpAddr ) -> bool{
+  match ip {
+    IpAddr::V4(ip) => {
+      let [ a, b, c, _ ] = ip.octets();
+
+      !( ip.is_private() || ip.is_loopback() || ip.is_link_local() || ip.is_broadcast() || ip.is_documentation()
+        || ip.is_unspecified() || ip.is_multicast()
+        || a == 0 || a >= 240
+        || ( a == 100 && ( 64..128 ).contains(&b) )
+        || ( a == 192 && b == 0 && c == 0 )
+        || ( a == 198 && ( b == 18 || b == 19 ) ) )
+    },
+    IpAddr::V6(ip) => {
+      if let Some(ip) = ip.to_ipv4_mapped() { return is_public(IpAddr::V4(ip)) }
+
+      let first = ip.segments()[0];
+
+      !( ip.is_loopback() || ip.is_unspecified() || ip.is_multicast()
+        || ( first & 0xfe00 ) == 0xfc00
+        || ( first & 0xffc0 ) == 0xfe80
+        || ( first == 0x2001 && ip.segments()[1] == 0xdb8 ) )
+    }
+  }
+}
+
+// Resolves the host ourselves so we can refuse internal addresses, the client is then pinned to what we checked
+async fn resolve( url: &Url ) -> anyhow::Result<Vec<SocketAddr>>{
+  let Some(host) = url.host_str() else { bail!("Webhook URL must have a host") };
+  let port = url.port_or_known_default().unwrap_or(443);
+
+  // IPv6 literals come back from host_str() in brackets
+  let host = host.trim_start_matches('[').trim_end_matches(']');
+
+  let addrs: Vec<SocketAddr> = tokio::net::lookup_host(( host, port )).await?.collect();
+  if addrs.is_empty() { bail!("Webhook host didn't resolve") }
+
+  if !allow_localhost(url) && addrs.iter().any(| x | !is_public(x.ip())) { bail!("Webhook host resolves to a non-public address") }
+
+  Ok(addrs)
+}
+
+// 30s, 1m, 2m, 4m... capped at 6 hours
+fn backoff( attempts: u32 ) -> i64{
+  ( 30 * 2_i64.pow(attempts.min(10)) ).min(21600)
+}
+
+// Queues an event for an app, does nothing if the app hasn't set up a webhook
+pub async fn send( app: Arc<AppHandler>, app_id: ObjectId, event: &str, data: Value ){
+  let oauth_app = app.oauth_apps.find_one(doc! { "_id": app_id }).await.unwrap();
+  if oauth_app.is_none_or(| x | x.webhook_url.is_none()){ return }
+
+  let now = Utc::now().timestamp();
+  let id = ObjectId::new();
+
+  let delivery = WebhookDelivery {
+    _id: id,
+
+    app_id,
+    event: event.to_owned(),
+    payload: json!({
+      "id": id.to_hex(),
+      "event": event,
+      "created_on": now,
+      "data": data
+    }).to_string(),
+
+    created_on: now,
+    next_attempt: now,
+    attempts: 0,
+
+    delivered: false,
+    failed: false,
+    last_error: None
+  };
+
+  app.webhook_deliveries.insert_one(&delivery).await.unwrap();
+
+  // Try straight away, the worker picks it up again if this fails
+  tokio::spawn(async move {
+    if let Err(err) = deliver(app, id).await { log::error!("Failed to deliver webhook {}: {}", id, err); }
+  });
+}
+
+// Sends the same event to every app the user has signed in to
+pub async fn send_to_user_apps( app: Arc<AppHandler>, user: &User, event: &str, data: Value ){
+  for app_id in &user.allowed_apps {
+    send(app.clone(), *app_id, event, data.clone()).await;
+  }
+}
+
+async fn deliver( app: Arc<AppHandler>, id: ObjectId ) -> anyhow::Result<()>{
+  let now = Utc::now().timestamp();
+
+  // Claim the delivery first so the worker and the first attempt can't both send it
+  let delivery = app.webhook_deliveries.find_one_and_update(
+    doc! { "_id": id, "delivered": false, "failed": false, "next_attempt": { "$lte": now } },
+    doc! { "$set": { "next_attempt": now + 60 } }
+  ).await?;
+
+  let Some(delivery) = delivery else { return Ok(()) };
+
+  let oauth_app = app.oauth_apps.find_one(doc! { "_id": delivery.app_id }).await?;
+
+  let Some(( url, secret )) = oauth_app.and_then(| x | Some(( x.webhook_url?, x.webhook_secret? ))) else {
+    app.webhook_deliveries.update_one(doc! { "_id": id }, doc! {
+      "$set": { "failed": true, "last_error": "App no longer has a webhook" }
+    }).await?;
+
+    return Ok(())
+  };
+
+  let res = post(&url, &secret, now, &delivery).await;
+
+  let error = match res {
+    Ok(res) if res.status().is_success() => None,
+    Ok(res) => Some(format!("HTTP {}", res.status().as_u16())),
+    Err(err) => Some(err.to_string())
+  };
+
+  let attempts = delivery.attempts + 1;
+
+  let update = match error {
+    None => doc! { "$set": { "delivered": true, "attempts": attempts, "last_error": None::<String> } },
+    Some(error) => doc! { "$set": {
+      "attempts": attempts,
+      "next_attempt": now + backoff(attempts),
+      "failed": attempts >= MAX_ATTEMPTS,
+      "last_error": error
+    } }
+  };
+
+  app.webhook_deliveries.update_one(doc! { "_id": id }, update).await?;
+  Ok(())
+}
+
+async fn post( url: &str, secret: &str, now: i64, delivery: &WebhookDelivery ) -> anyhow::Result<reqwest::Response>{
+  let url = check_url(url)?;
+  let addrs = resolve(&url).await?;
+
+  // Redirects could point anywhere, including back inside
+  let client = reqwest::Client::builder()
+    .timeout(Duration::from_secs(10))
+    .redirect(reqwest::redirect::Policy::none())
+    .resolve_to_addrs(url.host_str().unwrap(), &addrs)
+    .build()?;
+
+  Ok(client.post(url)
+    .header("Content-Type", "application/json")
+    .header("User-Agent", "PhazeID-Webhooks")
+    .header("X-PhazeID-Event", &delivery.event)
+    .header("X-PhazeID-Delivery", delivery._id.to_hex())
+    .header("X-PhazeID-Timestamp", now.to_string())
+    .header("X-PhazeID-Signature", format!("sha256={}", sign(secret, now, &delivery.payload)))
+    .body(delivery.payload.clone())
+    .send().await?)
+}
+
+// Background task started in main, retries anything that's due
+pub async fn worker( app: Arc<AppHandler> ){
+  loop {
+    tokio::time::sleep(Duration::from_secs(15)).await;
+
+    // Runs for as long as the server does, so a database hiccup just waits for the next pass
+    let due = match due(&app).await {
+      Ok(due) => due,
+      Err(err) => {
+        log::error!("Failed to find due webhooks: {}", err);
+        continue
+      }
+    };
+
+    for id in due {
+      if let Err(err) = deliver(app.clone(), id).await { log::error!("Failed to deliver webhook {}: {}", id, err); }
+    }
+  }
+}
+
+async fn due( app: &AppHandler ) -> anyhow::Result<Vec<ObjectId>>{
+  let now = Utc::now().timestamp();
+  let mut cursor = app.webhook_deliveries.find(doc! { "delivered": false, "failed": false, "next_attempt": { "$lte": now } }).await?;
+
+  let mut due = Vec::new();
+  while cursor.advance().await? {
+    due.push(cursor.deserialize_current()?._id);
+  }
+
+  Ok(due)
+}
+
+#[cfg(test)]
+mod tests {
+  use tokio::{ io::{ AsyncReadExt, AsyncWriteExt }, net::TcpListener };
+
+  use super::*;
+
+  #[test]
+  fn signs_payload(){
+    assert_eq!(
+      sign("whsec_test", 1700000000, r#"{"event":"user.updated"}"#),
+      "134770b4d1a55a4216642fa499eabb01a0cbb6d6bc2b184fba183f6acbe45a60"
+    );
+
+    assert_ne!(sign("whsec_test", 1700000001, r#"{"event":"user.updated"}"#), sign("whsec_test", 1700000000, r#"{"event":"user.updated"}"#));
+  }
+
+  #[test]
+  fn public_addresses(){
+    for ip in [ "1.1.1.1", "8.8.8.8", "2606:4700:4700::1111" ] {
+      assert!(is_public(ip.parse().unwrap()), "{}", ip);
+    }
+
+    for ip in [
+      "127.0.0.1", "10.0.0.1", "172.16.0.1", "192.168.1.1", "169.254.169.254", "0.0.0.0", "100.64.0.1",
+      "192.0.0.1", "198.18.0.1", "224.0.0.1", "255.255.255.255", "240.0.0.1",
+      "::1", "::", "fc00::1", "fd12::1", "fe80::1", "2001:db8::1", "ff02::1", "::ffff:127.0.0.1", "::ffff:169.254.169.254"
+    ] {
+      assert!(!is_public(ip.parse().unwrap()), "{}", ip);
+    }
+  }
+
+  #[test]
+  fn checks_url(){
+    assert!(check_url("https://example.com/hook").is_ok());
+
+    assert!(check_url("http://example.com/hook").is_err());
+    assert!(check_url("ftp://example.com/hook").is_err());
+    assert!(check_url("not a url").is_err());
+  }
+
+  #[tokio::test]
+  async fn refuses_internal_hosts(){
+    let delivery = delivery();
+
+    for url in [ "https://10.0.0.1/hook", "https://[::1]/hook", "https://169.254.169.254/latest/meta-data" ] {
+      let err = post(url, "whsec_test", 0, &delivery).await.unwrap_err();
+      assert!(err.to_string().contains("non-public"), "{}", url);
+    }
+  }
+
+  fn delivery() -> WebhookDelivery{
+    WebhookDelivery {
+      _id: ObjectId::new(),
+
+      app_id: ObjectId::new(),
+      event: "user.updated".into(),
+      payload: r#"{"event":"user.updated"}"#.into(),
+
+      created_on: 0,
+      next_attempt: 0,
+      attempts: 0,
+
+      delivered: false,
+      failed: false,
+      last_error: None
+    }
+  }
+
+  // Stand-in for an app's webhook endpoint, hands back the request it got
+  #[tokio::test]
+  async fn delivers_to_local_endpoint(){
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://127.0.0.1:{}/hook", listener.local_addr().unwrap().port());
+
+    assert!(check_url(&url).is_err());
+    env::set_var("WEBHOOK_ALLOW_LOCALHOST", "true");
+
+    let server = tokio::spawn(async move {
+      let ( mut socket, _ ) = listener.accept().await.unwrap();
+
+      // Headers and body can arrive separately
+      let mut req = vec![];
+      while !req.ends_with(b"}") {
+        let mut buf = vec![ 0u8; 8192 ];
+        let n = socket.read(&mut buf).await.unwrap();
+        if n == 0 { break }
+
+        req.extend_from_slice(&buf[..n]);
+      }
+
+      socket.write_all(b"HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n").await.unwrap();
+      String::from_utf8_lossy(&req).to_lowercase()
+    });
+
+    let delivery = delivery();
+    let res = post(&url, "whsec_test", 1700000000, &delivery).await.unwrap();
+    assert_eq!(res.status(), 204);
+
+    let req = server.await.unwrap();
+
+    assert!(req.starts_with("post /hook"));
+    assert!(req.contains("x-phazeid-event: user.updated"));
+    assert!(req.contains("x-phazeid-timestamp: 1700000000"));
+    assert!(req.contains("x-phazeid-signature: sha256=134770b4d1a55a4216642fa499eabb01a0cbb6d6bc2b184fba183f6acbe45a60"));
+    assert!(req.ends_with(r#"{"event":"user.updated"}"#));
+  }
+}
\ No newline at end of file